@@ -18,6 +18,7 @@ describe('testing AST', () => {
           end: 4,
           left: {
             type: 'Literal',
+            kind: 'number',
             start: 0,
             end: 1,
             value: 5,
@@ -26,6 +27,7 @@ describe('testing AST', () => {
           operator: '+',
           right: {
             type: 'Literal',
+            kind: 'number',
             start: 3,
             end: 4,
             value: 6,
@@ -56,6 +58,7 @@ describe('testing AST', () => {
             },
             init: {
               type: 'Literal',
+              kind: 'number',
               start: 14,
               end: 15,
               value: 5,
@@ -90,6 +93,7 @@ const newVar = myVar + 1
             },
             init: {
               type: 'Literal',
+              kind: 'number',
               start: 14,
               end: 15,
               value: 5,
@@ -127,6 +131,7 @@ const newVar = myVar + 1
               operator: '+',
               right: {
                 type: 'Literal',
+                kind: 'number',
                 start: 39,
                 end: 40,
                 value: 1,
@@ -318,6 +323,7 @@ const myVar = funcN(1, 2)`
               arguments: [
                 {
                   type: 'Literal',
+                  kind: 'number',
                   start: 58,
                   end: 59,
                   value: 1,
@@ -325,6 +331,7 @@ const myVar = funcN(1, 2)`
                 },
                 {
                   type: 'Literal',
+                  kind: 'number',
                   start: 61,
                   end: 62,
                   value: 2,
@@ -388,6 +395,7 @@ describe('testing pipe operator special', () => {
                       elements: [
                         {
                           type: 'Literal',
+                          kind: 'number',
                           start: 32,
                           end: 33,
                           value: 0,
@@ -395,6 +403,7 @@ describe('testing pipe operator special', () => {
                         },
                         {
                           type: 'Literal',
+                          kind: 'number',
                           start: 35,
                           end: 36,
                           value: 0,
@@ -424,6 +433,7 @@ describe('testing pipe operator special', () => {
                       elements: [
                         {
                           type: 'Literal',
+                          kind: 'number',
                           start: 52,
                           end: 53,
                           value: 2,
@@ -431,6 +441,7 @@ describe('testing pipe operator special', () => {
                         },
                         {
                           type: 'Literal',
+                          kind: 'number',
                           start: 55,
                           end: 56,
                           value: 3,
@@ -476,6 +487,7 @@ describe('testing pipe operator special', () => {
                             elements: [
                               {
                                 type: 'Literal',
+                                kind: 'number',
                                 start: 81,
                                 end: 82,
                                 value: 0,
@@ -483,6 +495,7 @@ describe('testing pipe operator special', () => {
                               },
                               {
                                 type: 'Literal',
+                                kind: 'number',
                                 start: 84,
                                 end: 85,
                                 value: 1,
@@ -503,6 +516,7 @@ describe('testing pipe operator special', () => {
                           },
                           value: {
                             type: 'Literal',
+                            kind: 'string',
                             start: 93,
                             end: 101,
                             value: 'myPath',
@@ -534,6 +548,7 @@ describe('testing pipe operator special', () => {
                       elements: [
                         {
                           type: 'Literal',
+                          kind: 'number',
                           start: 121,
                           end: 122,
                           value: 1,
@@ -541,6 +556,7 @@ describe('testing pipe operator special', () => {
                         },
                         {
                           type: 'Literal',
+                          kind: 'number',
                           start: 124,
                           end: 125,
                           value: 1,
@@ -566,6 +582,7 @@ describe('testing pipe operator special', () => {
                   arguments: [
                     {
                       type: 'Literal',
+                      kind: 'number',
                       start: 139,
                       end: 141,
                       value: 45,
@@ -617,6 +634,7 @@ describe('testing pipe operator special', () => {
                   end: 19,
                   left: {
                     type: 'Literal',
+                    kind: 'number',
                     start: 14,
                     end: 15,
                     value: 5,
@@ -625,6 +643,7 @@ describe('testing pipe operator special', () => {
                   operator: '+',
                   right: {
                     type: 'Literal',
+                    kind: 'number',
                     start: 18,
                     end: 19,
                     value: 6,
@@ -644,6 +663,7 @@ describe('testing pipe operator special', () => {
                   arguments: [
                     {
                       type: 'Literal',
+                      kind: 'number',
                       start: 30,
                       end: 32,
                       value: 45,
@@ -694,6 +714,7 @@ describe('testing pipe operator special', () => {
               elements: [
                 {
                   type: 'Literal',
+                  kind: 'number',
                   start: 12,
                   end: 13,
                   value: 1,
@@ -701,6 +722,7 @@ describe('testing pipe operator special', () => {
                 },
                 {
                   type: 'Literal',
+                  kind: 'string',
                   start: 15,
                   end: 18,
                   value: '2',
@@ -718,6 +740,7 @@ describe('testing pipe operator special', () => {
                   end: 32,
                   left: {
                     type: 'Literal',
+                    kind: 'number',
                     start: 27,
                     end: 28,
                     value: 4,
@@ -726,6 +749,7 @@ describe('testing pipe operator special', () => {
                   operator: '+',
                   right: {
                     type: 'Literal',
+                    kind: 'number',
                     start: 31,
                     end: 32,
                     value: 5,
@@ -764,6 +788,7 @@ describe('testing pipe operator special', () => {
             },
             init: {
               type: 'Literal',
+              kind: 'number',
               start: 14,
               end: 15,
               value: 3,
@@ -805,6 +830,7 @@ describe('testing pipe operator special', () => {
                   },
                   value: {
                     type: 'Literal',
+                    kind: 'string',
                     start: 34,
                     end: 39,
                     value: 'str',
@@ -823,6 +849,7 @@ describe('testing pipe operator special', () => {
                   },
                   value: {
                     type: 'Literal',
+                    kind: 'number',
                     start: 47,
                     end: 48,
                     value: 2,
@@ -862,6 +889,7 @@ describe('testing pipe operator special', () => {
                     end: 82,
                     left: {
                       type: 'Literal',
+                      kind: 'number',
                       start: 77,
                       end: 78,
                       value: 4,
@@ -870,6 +898,7 @@ describe('testing pipe operator special', () => {
                     operator: '+',
                     right: {
                       type: 'Literal',
+                      kind: 'number',
                       start: 81,
                       end: 82,
                       value: 5,
@@ -938,6 +967,7 @@ describe('testing pipe operator special', () => {
                         },
                         value: {
                           type: 'Literal',
+                          kind: 'string',
                           start: 27,
                           end: 34,
                           value: 'value',
@@ -996,6 +1026,7 @@ describe('testing pipe operator special', () => {
                     elements: [
                       {
                         type: 'Literal',
+                        kind: 'number',
                         start: 18,
                         end: 19,
                         value: 1,
@@ -1003,6 +1034,7 @@ describe('testing pipe operator special', () => {
                       },
                       {
                         type: 'Literal',
+                        kind: 'string',
                         start: 21,
                         end: 24,
                         value: '2',
@@ -1118,6 +1150,7 @@ describe('testing pipe operator special', () => {
               },
               property: {
                 type: 'Literal',
+                kind: 'string',
                 start: 20,
                 end: 25,
                 value: 'two',
@@ -1167,6 +1200,7 @@ describe('testing pipe operator special', () => {
                 },
                 property: {
                   type: 'Literal',
+                  kind: 'string',
                   start: 16,
                   end: 21,
                   value: 'one',
@@ -1213,6 +1247,7 @@ describe('nests binary expressions correctly', () => {
             end: 16,
             left: {
               type: 'Literal',
+              kind: 'number',
               start: 11,
               end: 12,
               value: 1,
@@ -1221,6 +1256,7 @@ describe('nests binary expressions correctly', () => {
             operator: '+',
             right: {
               type: 'Literal',
+              kind: 'number',
               start: 15,
               end: 16,
               value: 2,
@@ -1261,6 +1297,7 @@ describe('nests binary expressions correctly', () => {
               end: 16,
               left: {
                 type: 'Literal',
+                kind: 'number',
                 start: 11,
                 end: 12,
                 value: 1,
@@ -1269,6 +1306,7 @@ describe('nests binary expressions correctly', () => {
               operator: '*',
               right: {
                 type: 'Literal',
+                kind: 'number',
                 start: 15,
                 end: 16,
                 value: 2,
@@ -1278,6 +1316,7 @@ describe('nests binary expressions correctly', () => {
             operator: '+',
             right: {
               type: 'Literal',
+              kind: 'number',
               start: 19,
               end: 20,
               value: 3,
@@ -1314,6 +1353,7 @@ describe('nests binary expressions correctly', () => {
             end: 20,
             left: {
               type: 'Literal',
+              kind: 'number',
               start: 11,
               end: 12,
               value: 1,
@@ -1326,6 +1366,7 @@ describe('nests binary expressions correctly', () => {
               end: 20,
               left: {
                 type: 'Literal',
+                kind: 'number',
                 start: 15,
                 end: 16,
                 value: 2,
@@ -1334,6 +1375,7 @@ describe('nests binary expressions correctly', () => {
               operator: '*',
               right: {
                 type: 'Literal',
+                kind: 'number',
                 start: 19,
                 end: 20,
                 value: 3,
@@ -1358,6 +1400,7 @@ describe('nests binary expressions correctly', () => {
         end: 16,
         left: {
           type: 'Literal',
+          kind: 'number',
           start: 11,
           end: 12,
           value: 1,
@@ -1366,6 +1409,7 @@ describe('nests binary expressions correctly', () => {
         operator: '+',
         right: {
           type: 'Literal',
+          kind: 'number',
           start: 15,
           end: 16,
           value: 2,
@@ -1375,6 +1419,7 @@ describe('nests binary expressions correctly', () => {
       operator: '-',
       right: {
         type: 'Literal',
+        kind: 'number',
         start: 19,
         end: 20,
         value: 3,
@@ -1395,6 +1440,7 @@ describe('nests binary expressions correctly', () => {
         end: 16,
         left: {
           type: 'Literal',
+          kind: 'number',
           start: 11,
           end: 12,
           value: 1,
@@ -1403,6 +1449,7 @@ describe('nests binary expressions correctly', () => {
         operator: '*',
         right: {
           type: 'Literal',
+          kind: 'number',
           start: 15,
           end: 16,
           value: 2,
@@ -1412,6 +1459,7 @@ describe('nests binary expressions correctly', () => {
       operator: '/',
       right: {
         type: 'Literal',
+        kind: 'number',
         start: 19,
         end: 20,
         value: 3,
@@ -1433,7 +1481,14 @@ describe('nests binary expressions correctly', () => {
         operator: '+',
         start: 11,
         end: 30,
-        left: { type: 'Literal', value: 1, raw: '1', start: 11, end: 12 },
+        left: {
+          type: 'Literal',
+          kind: 'number',
+          value: 1,
+          raw: '1',
+          start: 11,
+          end: 12,
+        },
         right: {
           type: 'BinaryExpression',
           operator: '/',
@@ -1444,15 +1499,31 @@ describe('nests binary expressions correctly', () => {
             operator: '*',
             start: 15,
             end: 26,
-            left: { type: 'Literal', value: 2, raw: '2', start: 15, end: 16 },
+            left: {
+              type: 'Literal',
+              kind: 'number',
+              value: 2,
+              raw: '2',
+              start: 15,
+              end: 16,
+            },
             right: {
               type: 'BinaryExpression',
               operator: '-',
               start: 20,
               end: 25,
-              left: { type: 'Literal', value: 3, raw: '3', start: 20, end: 21 },
+              parenthesized: true,
+              left: {
+                type: 'Literal',
+                kind: 'number',
+                value: 3,
+                raw: '3',
+                start: 20,
+                end: 21,
+              },
               right: {
                 type: 'Literal',
+                kind: 'number',
                 value: 4,
                 raw: '4',
                 start: 24,
@@ -1460,10 +1531,24 @@ describe('nests binary expressions correctly', () => {
               },
             },
           },
-          right: { type: 'Literal', value: 5, raw: '5', start: 29, end: 30 },
+          right: {
+            type: 'Literal',
+            kind: 'number',
+            value: 5,
+            raw: '5',
+            start: 29,
+            end: 30,
+          },
         },
       },
-      right: { type: 'Literal', value: 6, raw: '6', start: 33, end: 34 },
+      right: {
+        type: 'Literal',
+        kind: 'number',
+        value: 6,
+        raw: '6',
+        start: 33,
+        end: 34,
+      },
     })
   })
 })
@@ -1564,8 +1649,22 @@ describe('test UnaryExpression', () => {
         end: 26,
         callee: { type: 'Identifier', start: 15, end: 18, name: 'min' },
         arguments: [
-          { type: 'Literal', start: 19, end: 20, value: 4, raw: '4' },
-          { type: 'Literal', start: 22, end: 25, value: 100, raw: '100' },
+          {
+            type: 'Literal',
+            kind: 'number',
+            start: 19,
+            end: 20,
+            value: 4,
+            raw: '4',
+          },
+          {
+            type: 'Literal',
+            kind: 'number',
+            start: 22,
+            end: 25,
+            value: 100,
+            raw: '100',
+          },
         ],
         function: expect.any(Object),
         optional: false,
@@ -1585,21 +1684,49 @@ describe('testing nested call expressions', () => {
       end: 40,
       callee: { type: 'Identifier', start: 14, end: 17, name: 'min' },
       arguments: [
-        { type: 'Literal', start: 18, end: 21, value: 100, raw: '100' },
+        {
+          type: 'Literal',
+          kind: 'number',
+          start: 18,
+          end: 21,
+          value: 100,
+          raw: '100',
+        },
         {
           type: 'BinaryExpression',
           operator: '+',
           start: 23,
           end: 39,
-          left: { type: 'Literal', value: 1, raw: '1', start: 23, end: 24 },
+          left: {
+            type: 'Literal',
+            kind: 'number',
+            value: 1,
+            raw: '1',
+            start: 23,
+            end: 24,
+          },
           right: {
             type: 'CallExpression',
             start: 27,
             end: 39,
             callee: { type: 'Identifier', start: 27, end: 33, name: 'legLen' },
             arguments: [
-              { type: 'Literal', start: 34, end: 35, value: 5, raw: '5' },
-              { type: 'Literal', start: 37, end: 38, value: 3, raw: '3' },
+              {
+                type: 'Literal',
+                kind: 'number',
+                start: 34,
+                end: 35,
+                value: 5,
+                raw: '5',
+              },
+              {
+                type: 'Literal',
+                kind: 'number',
+                start: 37,
+                end: 38,
+                value: 3,
+                raw: '3',
+              },
             ],
             function: expect.any(Object),
             optional: false,
@@ -1631,6 +1758,7 @@ describe('should recognise callExpresions in binaryExpressions', () => {
           arguments: [
             {
               type: 'Literal',
+              kind: 'string',
               start: 16,
               end: 23,
               value: 'seg02',
@@ -1641,7 +1769,14 @@ describe('should recognise callExpresions in binaryExpressions', () => {
           function: expect.any(Object),
           optional: false,
         },
-        right: { type: 'Literal', value: 1, raw: '1', start: 30, end: 31 },
+        right: {
+          type: 'Literal',
+          kind: 'number',
+          value: 1,
+          raw: '1',
+          start: 30,
+          end: 31,
+        },
       },
       { type: 'PipeSubstitution', start: 33, end: 34 },
     ])
@@ -0,0 +1,1186 @@
+//! Read-only queries over the AST.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{
+        diagnostics::SourceMap,
+        types::{
+            BinaryOperator, BinaryPart, BodyItem, FormatOptions, Literal, LiteralIdentifier, NonCodeMeta, NonCodeValue,
+            PipeExpression, Program, UnaryOperator, Value, VariableDeclarator,
+        },
+        walk::{self, Change, NodeRef},
+    },
+    executor::SourceRange,
+};
+
+/// Group the pipe expressions in a program by the variable they are assigned to.
+/// Every top-level declaration whose initializer contains at least one pipe gets an
+/// entry, with its pipes listed in source order (nested pipes included).
+pub fn pipes_by_declaration(program: &Program) -> HashMap<String, Vec<&PipeExpression>> {
+    let mut pipes: HashMap<String, Vec<&PipeExpression>> = HashMap::new();
+    for item in &program.body {
+        let BodyItem::VariableDeclaration(declaration) = item else {
+            continue;
+        };
+        for declarator in &declaration.declarations {
+            NodeRef::from(&declarator.init).walk(&mut |node| {
+                if let NodeRef::PipeExpression(pipe) = node {
+                    pipes.entry(declarator.id.name.to_string()).or_default().push(pipe);
+                }
+            });
+        }
+    }
+    pipes
+}
+
+/// The statements a top-level statement depends on, directly or transitively.
+/// Starting from the statement at `offset`, this follows identifier references back through
+/// the earlier declarations of the program. The statement at `offset` itself is not included,
+/// and the result is in source order.
+pub fn backward_slice(program: &Program, offset: usize) -> Vec<&BodyItem> {
+    let Some(target) = program
+        .body
+        .iter()
+        .position(|item| item.start() <= offset && offset <= item.end())
+    else {
+        return vec![];
+    };
+
+    let mut wanted = free_names((&program.body[target]).into());
+    let mut slice = Vec::new();
+    for item in program.body[..target].iter().rev() {
+        let BodyItem::VariableDeclaration(declaration) = item else {
+            continue;
+        };
+        let mut needed = false;
+        for declarator in &declaration.declarations {
+            if wanted.remove(declarator.id.name.as_str()) {
+                needed = true;
+                wanted.extend(free_names((&declarator.init).into()));
+            }
+        }
+        if needed {
+            slice.push(item);
+        }
+    }
+    slice.reverse();
+    slice
+}
+
+/// Whether running the program twice is guaranteed to give the same result.
+/// This is false if anything in the program, including inside function bodies, calls one of
+/// the `nondeterministic_fns` (e.g. `random` or `now`).
+pub fn is_deterministic(program: &Program, nondeterministic_fns: &HashSet<String>) -> bool {
+    let mut deterministic = true;
+    walk::walk(program, |node| {
+        if let NodeRef::CallExpression(call) = node {
+            if nondeterministic_fns.contains(call.callee.name.as_str()) {
+                deterministic = false;
+            }
+        }
+    });
+    deterministic
+}
+
+/// The deepest chain of lexically nested function expressions in the program.
+/// A program with no functions has depth 0, and a top-level function has depth 1.
+pub fn max_function_nesting(program: &Program) -> usize {
+    fn depth(node: NodeRef<'_>) -> usize {
+        let mut deepest = 0;
+        node.for_each_child(|child| deepest = deepest.max(depth(child)));
+        match node {
+            NodeRef::FunctionExpression(_) => deepest + 1,
+            _ => deepest,
+        }
+    }
+
+    depth(NodeRef::Program(program))
+}
+
+/// The top-level declarations that nothing else in the program refers to, in source order.
+/// A declaration whose initializer calls a function is never reported: the call might have
+/// side effects (like drawing a sketch), so it's worth keeping even if its result is unused.
+pub fn unused_declarations(program: &Program) -> Vec<&VariableDeclarator> {
+    let used: HashSet<&str> = program.body.iter().flat_map(|item| free_names(item.into())).collect();
+    program
+        .body
+        .iter()
+        .filter_map(|item| match item {
+            BodyItem::VariableDeclaration(declaration) => Some(declaration),
+            _ => None,
+        })
+        .flat_map(|declaration| &declaration.declarations)
+        .filter(|declarator| !used.contains(declarator.id.name.as_str()) && !calls_anything(&declarator.init))
+        .collect()
+}
+
+/// Whether evaluating a value calls a function.
+/// A function body isn't evaluated until the function is called, so calls inside one don't count.
+pub(crate) fn calls_anything(value: &Value) -> bool {
+    fn visit(node: NodeRef<'_>) -> bool {
+        match node {
+            NodeRef::CallExpression(_) => true,
+            NodeRef::FunctionExpression(_) => false,
+            _ => {
+                let mut found = false;
+                node.for_each_child(|child| found |= visit(child));
+                found
+            }
+        }
+    }
+
+    visit(value.into())
+}
+
+/// How much of each top-level function is comments: the length of the comment text inside it
+/// (including in nested pipes and functions) divided by the length of the whole function.
+pub fn comment_density(program: &Program) -> HashMap<String, f32> {
+    fn comment_len(meta: &NonCodeMeta) -> usize {
+        meta.start
+            .iter()
+            .chain(meta.non_code_nodes.values().flatten())
+            .filter(|node| node.value != NonCodeValue::NewLine)
+            .map(|node| node.value().len())
+            .sum()
+    }
+
+    let mut densities = HashMap::new();
+    for item in &program.body {
+        let BodyItem::VariableDeclaration(declaration) = item else {
+            continue;
+        };
+        for declarator in &declaration.declarations {
+            let Value::FunctionExpression(function) = &declarator.init else {
+                continue;
+            };
+            let mut comments = 0;
+            NodeRef::FunctionExpression(function).walk(&mut |node| match node {
+                NodeRef::Program(body) => comments += comment_len(&body.non_code_meta),
+                NodeRef::PipeExpression(pipe) => comments += comment_len(&pipe.non_code_meta),
+                _ => {}
+            });
+            let len = function.end.saturating_sub(function.start).max(1);
+            densities.insert(declarator.id.name.to_string(), comments as f32 / len as f32);
+        }
+    }
+    densities
+}
+
+/// The names that can be used at `offset`, with the spans that declare them.
+/// That's everything declared before `offset` in the program and in each function body it's
+/// inside, plus those functions' parameters. An inner name hides the same name from an outer
+/// scope. Names are listed outermost scope first, in the order they're declared.
+pub fn names_in_scope(program: &Program, offset: usize) -> Vec<(String, SourceRange)> {
+    let mut names: Vec<(&str, SourceRange)> = Vec::new();
+    let mut scope = Some(program);
+    while let Some(body) = scope.take() {
+        for item in &body.body {
+            if item.end() <= offset {
+                if let BodyItem::VariableDeclaration(declaration) = item {
+                    for declarator in &declaration.declarations {
+                        names.push((declarator.id.name.as_str(), SourceRange::from(&declarator.id)));
+                    }
+                }
+                continue;
+            }
+
+            // This statement is at or after the offset. If the offset is inside a function in
+            // it, that function's body is the next scope in.
+            if item.start() <= offset {
+                let mut function = None;
+                NodeRef::from(item).walk(&mut |node| {
+                    if let NodeRef::FunctionExpression(f) = node {
+                        if function.is_none() && f.start <= offset && offset <= f.end {
+                            function = Some(f);
+                        }
+                    }
+                });
+                if let Some(function) = function {
+                    for param in &function.params {
+                        names.push((param.name.as_str(), SourceRange::from(param)));
+                    }
+                    scope = Some(&function.body);
+                }
+            }
+            break;
+        }
+    }
+
+    names
+        .iter()
+        .enumerate()
+        .filter(|(i, (name, _))| !names[i + 1..].iter().any(|(inner, _)| inner == name))
+        .map(|(_, (name, source_range))| (name.to_string(), *source_range))
+        .collect()
+}
+
+/// The smallest statement that contains all of `selection`, for snapping a selection out to a
+/// whole statement. Statements inside function bodies count. When the selection covers more
+/// than one statement of a body, that body is returned instead. `None` if the selection isn't
+/// inside the program.
+pub fn enclosing_statement(program: &Program, selection: SourceRange) -> Option<SourceRange> {
+    let contains = |start: usize, end: usize| start <= selection.start() && selection.end() <= end;
+    if !contains(program.start, program.end) {
+        return None;
+    }
+
+    let mut body = program;
+    loop {
+        let Some(item) = body.body.iter().find(|item| contains(item.start(), item.end())) else {
+            return Some(SourceRange([body.start, body.end]));
+        };
+        // If the selection is inside a function body in this statement, look there next.
+        let mut function = None;
+        NodeRef::from(item).walk(&mut |node| {
+            if let NodeRef::FunctionExpression(f) = node {
+                if function.is_none() && contains(f.body.start, f.body.end) {
+                    function = Some(f);
+                }
+            }
+        });
+        match function {
+            Some(function) => body = &function.body,
+            None => return Some(item.into()),
+        }
+    }
+}
+
+/// The distinct sets of keys the program's objects have, each sorted, in the order they first
+/// appear. Objects nested anywhere (including inside other objects) are included.
+pub fn object_shapes(program: &Program) -> Vec<Vec<String>> {
+    let mut shapes: Vec<Vec<String>> = Vec::new();
+    walk::walk(program, |node| {
+        let NodeRef::ObjectExpression(object) = node else {
+            return;
+        };
+        let mut keys: Vec<String> = object
+            .properties
+            .iter()
+            .map(|property| property.key.name.to_string())
+            .collect();
+        keys.sort();
+        keys.dedup();
+        if !shapes.contains(&keys) {
+            shapes.push(keys);
+        }
+    });
+    shapes
+}
+
+/// The binary operators the program uses anywhere.
+pub fn operators_used(program: &Program) -> HashSet<BinaryOperator> {
+    let mut operators = HashSet::new();
+    walk::walk(program, |node| {
+        if let NodeRef::BinaryExpression(binary) = node {
+            operators.insert(binary.operator.clone());
+        }
+    });
+    operators
+}
+
+/// The unary operators the program uses anywhere.
+pub fn unary_operators_used(program: &Program) -> HashSet<UnaryOperator> {
+    let mut operators = HashSet::new();
+    walk::walk(program, |node| {
+        if let NodeRef::UnaryExpression(unary) = node {
+            operators.insert(unary.operator.clone());
+        }
+    });
+    operators
+}
+
+/// The names of the functions each pipe calls, stage by stage, for every pipe in the program
+/// in source order (nested pipes included). Calls can't be made on members, so a pipe is the
+/// only way KCL chains calls; stages that aren't calls are left out.
+pub fn call_chains(program: &Program) -> Vec<Vec<String>> {
+    let mut chains = Vec::new();
+    walk::walk(program, |node| {
+        let NodeRef::PipeExpression(pipe) = node else {
+            return;
+        };
+        chains.push(
+            pipe.body
+                .iter()
+                .filter_map(|stage| match stage {
+                    Value::CallExpression(call) => Some(call.callee.name.to_string()),
+                    _ => None,
+                })
+                .collect(),
+        );
+    });
+    chains
+}
+
+/// The kind of value passed as an argument, as far as can be told without running the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArgType {
+    Number,
+    String,
+    Bool,
+    Object,
+    Array,
+    /// Anything that isn't written out as a constant: a name, a call, `%`, arithmetic and so on.
+    Unknown,
+}
+
+impl ArgType {
+    /// The type of `value`, where `bound` are the names declared in the scopes it's in.
+    fn of(value: &Value, bound: &[&str]) -> Self {
+        let is_number = |literal: &Literal| literal.value.is_number() || literal.special_number().is_some();
+        match value {
+            Value::Literal(literal) if is_number(literal) => ArgType::Number,
+            Value::Literal(literal) if literal.value.is_string() => ArgType::String,
+            // The parser reads `true` and `false` as identifiers, which a declaration or
+            // parameter of the same name would hide.
+            Value::Identifier(identifier)
+                if matches!(identifier.name.as_str(), "true" | "false")
+                    && !bound.contains(&identifier.name.as_str()) =>
+            {
+                ArgType::Bool
+            }
+            // A negative number is written as `-` applied to a number.
+            Value::UnaryExpression(unary)
+                if unary.operator == UnaryOperator::Neg
+                    && matches!(&unary.argument, BinaryPart::Literal(literal) if is_number(literal)) =>
+            {
+                ArgType::Number
+            }
+            Value::ObjectExpression(_) => ArgType::Object,
+            Value::ArrayExpression(_) => ArgType::Array,
+            _ => ArgType::Unknown,
+        }
+    }
+}
+
+/// The types seen passed in one argument position.
+pub type ArgTypeSet = HashSet<ArgType>;
+
+/// For every function the program calls, the types passed in each argument position across all
+/// of its calls. A position only some calls pass an argument in has the types of those calls.
+pub fn call_arg_type_profiles(program: &Program) -> HashMap<String, Vec<ArgTypeSet>> {
+    fn visit<'a>(node: NodeRef<'a>, bound: &mut Vec<&'a str>, profiles: &mut HashMap<String, Vec<ArgTypeSet>>) {
+        let outer = bound.len();
+        match node {
+            NodeRef::Program(body) => bound.extend(
+                body.body
+                    .iter()
+                    .filter_map(|item| match item {
+                        BodyItem::VariableDeclaration(declaration) => Some(&declaration.declarations),
+                        _ => None,
+                    })
+                    .flatten()
+                    .map(|declarator| declarator.id.name.as_str()),
+            ),
+            NodeRef::FunctionExpression(function) => {
+                bound.extend(function.params.iter().map(|param| param.name.as_str()));
+            }
+            NodeRef::CallExpression(call) => {
+                let profile = profiles.entry(call.callee.name.to_string()).or_default();
+                if profile.len() < call.arguments.len() {
+                    profile.resize_with(call.arguments.len(), Default::default);
+                }
+                for (types, arg) in profile.iter_mut().zip(&call.arguments) {
+                    types.insert(ArgType::of(arg, bound));
+                }
+            }
+            _ => {}
+        }
+        node.for_each_child(|child| visit(child, bound, profiles));
+        bound.truncate(outer);
+    }
+
+    let mut profiles = HashMap::new();
+    visit(NodeRef::Program(program), &mut Vec::new(), &mut profiles);
+    profiles
+}
+
+/// A short string describing the shape of a program, for spotting files that are the same apart
+/// from their numbers and names: the kind of each top-level statement in order, then every
+/// function called anywhere, sorted, like `const,const,expr|line,show`.
+pub fn structure_fingerprint(program: &Program) -> String {
+    let kinds: Vec<String> = program
+        .body
+        .iter()
+        .map(|item| match item {
+            BodyItem::VariableDeclaration(declaration) => declaration.kind.to_string(),
+            BodyItem::ExpressionStatement(_) => "expr".to_owned(),
+            BodyItem::ReturnStatement(_) => "return".to_owned(),
+        })
+        .collect();
+    let mut callees: Vec<&str> = walk::iter_calls(program)
+        .map(|call| call.callee.name.as_str())
+        .collect();
+    callees.sort_unstable();
+    callees.dedup();
+    format!("{}|{}", kinds.join(","), callees.join(","))
+}
+
+/// The pipe with the most stages, anywhere in the program. Ties go to the one that starts first.
+pub fn longest_pipe(program: &Program) -> Option<&PipeExpression> {
+    let mut longest: Option<&PipeExpression> = None;
+    walk::walk(program, |node| {
+        let NodeRef::PipeExpression(pipe) = node else {
+            return;
+        };
+        if !matches!(longest, Some(longest) if longest.body.len() >= pipe.body.len()) {
+            longest = Some(pipe);
+        }
+    });
+    longest
+}
+
+/// The most deeply nested node in the program and how deep it is, counting the program itself
+/// as depth 0. Ties go to the one that starts first. `None` for an empty program.
+pub fn deepest_node(program: &Program) -> Option<(NodeRef<'_>, usize)> {
+    fn visit<'a>(node: NodeRef<'a>, depth: usize, deepest: &mut Option<(NodeRef<'a>, usize)>) {
+        if !matches!(deepest, Some((_, deepest)) if *deepest >= depth) {
+            *deepest = Some((node, depth));
+        }
+        node.for_each_child(|child| visit(child, depth + 1, deepest));
+    }
+
+    if program.body.is_empty() {
+        return None;
+    }
+    let mut deepest = None;
+    visit(NodeRef::Program(program), 0, &mut deepest);
+    deepest
+}
+
+/// Map each top-level declaration to the top-level names its initializer reads.
+/// Names that aren't declared at the top level (standard library functions, say) are left out,
+/// but a declaration that reads itself keeps that edge, so [`cycles`] can report it.
+pub fn dependency_graph(program: &Program) -> HashMap<String, HashSet<String>> {
+    let declarators: Vec<&VariableDeclarator> = program
+        .body
+        .iter()
+        .filter_map(|item| match item {
+            BodyItem::VariableDeclaration(declaration) => Some(&declaration.declarations),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    let declared: HashSet<&str> = declarators
+        .iter()
+        .map(|declarator| declarator.id.name.as_str())
+        .collect();
+
+    let mut graph: HashMap<String, HashSet<String>> = HashMap::new();
+    for declarator in declarators {
+        let dependencies = graph.entry(declarator.id.name.to_string()).or_default();
+        for name in free_names((&declarator.init).into()) {
+            if declared.contains(name) {
+                dependencies.insert(name.to_owned());
+            }
+        }
+    }
+    graph
+}
+
+/// The groups of names in a [`dependency_graph`] that depend on each other, each sorted, in
+/// sorted order. A name that only depends on itself is a group of one.
+pub fn cycles(graph: &HashMap<String, HashSet<String>>) -> Vec<Vec<String>> {
+    // Tarjan's strongly connected components, visiting names in sorted order.
+    struct State<'a> {
+        graph: &'a HashMap<String, HashSet<String>>,
+        index: HashMap<&'a str, usize>,
+        low: HashMap<&'a str, usize>,
+        stack: Vec<&'a str>,
+        on_stack: HashSet<&'a str>,
+        cycles: Vec<Vec<String>>,
+    }
+
+    fn visit<'a>(name: &'a str, state: &mut State<'a>) {
+        let graph = state.graph;
+        let index = state.index.len();
+        state.index.insert(name, index);
+        state.low.insert(name, index);
+        state.stack.push(name);
+        state.on_stack.insert(name);
+
+        let mut dependencies: Vec<&str> = graph[name]
+            .iter()
+            .map(String::as_str)
+            .filter(|dependency| graph.contains_key(*dependency))
+            .collect();
+        dependencies.sort_unstable();
+        for dependency in dependencies {
+            if !state.index.contains_key(dependency) {
+                visit(dependency, state);
+                let low = state.low[name].min(state.low[dependency]);
+                state.low.insert(name, low);
+            } else if state.on_stack.contains(dependency) {
+                let low = state.low[name].min(state.index[dependency]);
+                state.low.insert(name, low);
+            }
+        }
+
+        if state.low[name] != index {
+            return;
+        }
+        let mut component = Vec::new();
+        while let Some(member) = state.stack.pop() {
+            state.on_stack.remove(member);
+            component.push(member.to_owned());
+            if member == name {
+                break;
+            }
+        }
+        if component.len() > 1 || graph[name].contains(name) {
+            component.sort();
+            state.cycles.push(component);
+        }
+    }
+
+    let mut state = State {
+        graph,
+        index: HashMap::new(),
+        low: HashMap::new(),
+        stack: Vec::new(),
+        on_stack: HashSet::new(),
+        cycles: Vec::new(),
+    };
+    let mut names: Vec<&str> = graph.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    for name in names {
+        if !state.index.contains_key(name) {
+            visit(name, &mut state);
+        }
+    }
+    state.cycles.sort();
+    state.cycles
+}
+
+/// One line per difference between two programs, like "changed literal 5→10 at line 3",
+/// for showing a user what an edit did. Lines are counted from 1 in `new_src`, the source
+/// `new` was parsed from. See [`walk::diff`] for how differences are found.
+pub fn diff_summary(old: &Program, new: &Program, new_src: &str) -> Vec<String> {
+    let source_map = SourceMap::new(new_src);
+    let line = |node: NodeRef<'_>| source_map.line_col(node.start()).0 + 1;
+
+    walk::diff(old, new)
+        .into_iter()
+        .map(|change| match change {
+            Change::Replaced {
+                old: NodeRef::Literal(a),
+                new: new @ NodeRef::Literal(b),
+            } => format!("changed literal {}→{} at line {}", a.raw, b.raw, line(new)),
+            Change::Replaced {
+                old: NodeRef::Identifier(a),
+                new: new @ NodeRef::Identifier(b),
+            } => format!("renamed `{}` to `{}` at line {}", a.name, b.name, line(new)),
+            Change::Replaced {
+                old: NodeRef::BinaryExpression(a),
+                new: new @ NodeRef::BinaryExpression(b),
+            } => format!("changed operator {}→{} at line {}", a.operator, b.operator, line(new)),
+            Change::Replaced { old, new } if node_kind(old) == node_kind(new) => {
+                format!("changed {} at line {}", node_kind(new), line(new))
+            }
+            Change::Replaced { old, new } => {
+                format!(
+                    "replaced {} with {} at line {}",
+                    node_kind(old),
+                    node_kind(new),
+                    line(new)
+                )
+            }
+            Change::Added(node) => format!("added {} at line {}", node_kind(node), line(node)),
+            Change::Removed(node) => format!("removed {}", node_kind(node)),
+        })
+        .collect()
+}
+
+/// What to call a node in a message.
+fn node_kind(node: NodeRef<'_>) -> &'static str {
+    match node {
+        NodeRef::Program(_) => "body",
+        NodeRef::ExpressionStatement(_) => "statement",
+        NodeRef::VariableDeclaration(_) | NodeRef::VariableDeclarator(_) => "declaration",
+        NodeRef::ReturnStatement(_) => "return statement",
+        NodeRef::Literal(_) => "literal",
+        NodeRef::Identifier(_) => "identifier",
+        NodeRef::BinaryExpression(_) => "binary expression",
+        NodeRef::FunctionExpression(_) => "function",
+        NodeRef::CallExpression(_) => "call",
+        NodeRef::PipeExpression(_) => "pipe expression",
+        NodeRef::PipeSubstitution(_) => "`%`",
+        NodeRef::ArrayExpression(_) => "array",
+        NodeRef::ObjectExpression(_) => "object",
+        NodeRef::ObjectProperty(_) => "property",
+        NodeRef::MemberExpression(_) => "member expression",
+        NodeRef::UnaryExpression(_) => "unary expression",
+    }
+}
+
+/// Roughly how long `program.recast(options, 0)` would be, without building the string.
+/// This adds up the length of each node's own tokens and its indentation, following the
+/// recaster's layout rules closely enough to usually be within a few percent.
+pub fn estimated_recast_len(program: &Program, options: &FormatOptions) -> usize {
+    let len = estimate_body(program, options, 0);
+    if options.insert_final_newline {
+        len
+    } else {
+        len.saturating_sub(1)
+    }
+}
+
+fn indentation_len(options: &FormatOptions, level: usize) -> usize {
+    if options.use_tabs {
+        level
+    } else {
+        level * options.tab_size
+    }
+}
+
+/// The statements of a body, one per line, with their comments.
+fn estimate_body(body: &Program, options: &FormatOptions, level: usize) -> usize {
+    let indentation = indentation_len(options, level);
+    let statements: usize = body
+        .body
+        .iter()
+        .map(|item| indentation + estimate(item.into(), options, level) + 1)
+        .sum();
+    statements + estimate_non_code(&body.non_code_meta, indentation)
+}
+
+fn estimate_non_code(meta: &NonCodeMeta, indentation: usize) -> usize {
+    meta.start
+        .iter()
+        .chain(meta.non_code_nodes.values().flatten())
+        .map(|node| match node.value {
+            NonCodeValue::NewLine => 1,
+            // The comment markers, a space either side of the text, and a line break.
+            _ => indentation + node.value().len() + 5,
+        })
+        .sum()
+}
+
+fn estimate(node: NodeRef<'_>, options: &FormatOptions, level: usize) -> usize {
+    let children = |level: usize| {
+        let mut len = 0;
+        node.for_each_child(|child| len += estimate(child, options, level));
+        len
+    };
+    let separators = |count: usize| count.saturating_sub(1) * 2;
+
+    match node {
+        NodeRef::Program(body) => estimate_body(body, options, level),
+        NodeRef::ExpressionStatement(_) => children(level),
+        NodeRef::VariableDeclaration(declaration) => {
+            let keyword = declaration.kind.to_string().len() + 1;
+            declaration.declarations.len() * keyword + children(level)
+        }
+        NodeRef::VariableDeclarator(declarator) => {
+            declarator.id.name.len() + " = ".len() + estimate((&declarator.init).into(), options, level)
+        }
+        NodeRef::ReturnStatement(_) => "return ".len() + children(level),
+        NodeRef::Literal(literal) => literal.raw.len(),
+        NodeRef::Identifier(identifier) => identifier.name.len(),
+        NodeRef::BinaryExpression(binary) => binary.operator.to_string().len() + 2 + children(level),
+        NodeRef::FunctionExpression(function) => {
+            // `(params) => {`, the body one level in, then `}` on its own line.
+            let params: usize = function.params.iter().map(|param| param.name.len()).sum();
+            "() => {\n}".len()
+                + params
+                + separators(function.params.len())
+                + estimate_body(&function.body, options, level + 1)
+        }
+        NodeRef::CallExpression(call) => "()".len() + separators(call.arguments.len()) + children(level),
+        NodeRef::PipeExpression(pipe) => {
+            // Every stage after the first goes on its own line, after `|> `.
+            let line_start = 1 + indentation_len(options, level + 1) + "|> ".len();
+            pipe.body.len().saturating_sub(1) * line_start
+                + children(level + 1)
+                + estimate_non_code(&pipe.non_code_meta, 0)
+        }
+        NodeRef::PipeSubstitution(_) => 1,
+        NodeRef::ArrayExpression(array) => {
+            let elements = children(level);
+            let flat = "[]".len() + separators(array.elements.len()) + elements;
+            if flat > 40 {
+                // One element per line, one level in.
+                "[\n\n]".len()
+                    + elements
+                    + array.elements.len() * (indentation_len(options, level + 1) + 2)
+                    + indentation_len(options, level)
+            } else {
+                flat
+            }
+        }
+        NodeRef::ObjectExpression(object) => {
+            let properties = children(level);
+            let flat = "{  }".len() + separators(object.properties.len()) + properties;
+            if flat > 40 {
+                "{\n\n}".len()
+                    + properties
+                    + object.properties.len() * (indentation_len(options, level + 1) + 2)
+                    + indentation_len(options, level)
+            } else {
+                flat
+            }
+        }
+        NodeRef::ObjectProperty(_) => ": ".len() + children(level),
+        NodeRef::MemberExpression(member) => {
+            // Either `.name` or `[property]`.
+            let punctuation = match (&member.property, member.computed) {
+                (LiteralIdentifier::Identifier(_), false) => 1,
+                _ => 2,
+            };
+            punctuation + children(level)
+        }
+        NodeRef::UnaryExpression(unary) => unary.operator.to_string().len() + children(level),
+    }
+}
+
+/// The names a node reads from its enclosing scope.
+/// Declared names, object keys and `.property` names aren't references, and names bound
+/// inside a function (its parameters and local declarations) don't escape it.
+pub(crate) fn free_names(node: NodeRef<'_>) -> HashSet<&str> {
+    let mut names = HashSet::new();
+    collect_free_names(node, &mut names);
+    names
+}
+
+fn collect_free_names<'a>(node: NodeRef<'a>, names: &mut HashSet<&'a str>) {
+    match node {
+        NodeRef::Identifier(identifier) => {
+            names.insert(identifier.name.as_str());
+        }
+        NodeRef::VariableDeclarator(declarator) => collect_free_names((&declarator.init).into(), names),
+        NodeRef::ObjectProperty(property) => collect_free_names((&property.value).into(), names),
+        NodeRef::MemberExpression(member) => {
+            collect_free_names((&member.object).into(), names);
+            if let (true, LiteralIdentifier::Identifier(identifier)) = (member.computed, &member.property) {
+                names.insert(identifier.name.as_str());
+            }
+        }
+        NodeRef::FunctionExpression(function) => {
+            let mut inner = HashSet::new();
+            collect_free_names(NodeRef::Program(&function.body), &mut inner);
+            for param in &function.params {
+                inner.remove(param.name.as_str());
+            }
+            for item in &function.body.body {
+                if let BodyItem::VariableDeclaration(declaration) = item {
+                    for declarator in &declaration.declarations {
+                        inner.remove(declarator.id.name.as_str());
+                    }
+                }
+            }
+            names.extend(inner);
+        }
+        _ => node.for_each_child(|child| collect_free_names(child, names)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(code: &str) -> Program {
+        let tokens = crate::token::lexer(code);
+        crate::parser::Parser::new(tokens).ast().unwrap()
+    }
+
+    #[test]
+    fn test_pipes_by_declaration() {
+        let code = r#"const part001 = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> line([0, 10], %)
+const part002 = startSketchOn('XZ')
+  |> startProfileAt([5, 5], %)
+  |> line([10, 0], %)
+  |> close(%)
+const length = 10
+"#;
+        let program = parse(code);
+        let pipes = pipes_by_declaration(&program);
+
+        assert_eq!(pipes.len(), 2);
+        assert_eq!(pipes["part001"].len(), 1);
+        assert_eq!(pipes["part001"][0].body.len(), 3);
+        assert_eq!(pipes["part002"].len(), 1);
+        assert_eq!(pipes["part002"][0].body.len(), 4);
+        assert!(!pipes.contains_key("length"));
+    }
+
+    #[test]
+    fn test_backward_slice() {
+        let code = r#"const width = 10
+const unused = 3
+fn double = (n) => {
+  return n * 2
+}
+const height = double(width)
+const area = width * height
+"#;
+        let program = parse(code);
+        let offset = code.find("area").unwrap();
+        let slice = backward_slice(&program, offset);
+        assert_eq!(slice.len(), 3);
+        assert_eq!(slice[0], &program.body[0]);
+        assert_eq!(slice[1], &program.body[2]);
+        assert_eq!(slice[2], &program.body[3]);
+
+        // `height` only needs two of the three declarations before it.
+        let offset = code.find("const height").unwrap();
+        let slice = backward_slice(&program, offset);
+        assert_eq!(slice, vec![&program.body[0], &program.body[2]]);
+    }
+
+    #[test]
+    fn test_is_deterministic() {
+        let nondeterministic_fns: HashSet<String> = ["random".to_string(), "now".to_string()].into();
+
+        let program = parse(
+            "const x = 2 * pi()
+const y = sqrt(x)",
+        );
+        assert!(is_deterministic(&program, &nondeterministic_fns));
+
+        let program = parse("fn jitter = (x) => {\n  return x + random()\n}\nconst y = jitter(1)");
+        assert!(!is_deterministic(&program, &nondeterministic_fns));
+    }
+
+    #[test]
+    fn test_max_function_nesting() {
+        assert_eq!(max_function_nesting(&parse("const x = 1")), 0);
+
+        let code = r#"fn outer = (a) => {
+  fn middle = (b) => {
+    fn inner = (c) => {
+      return c
+    }
+    return inner(b)
+  }
+  return middle(a)
+}
+fn flat = (x) => {
+  return x
+}
+"#;
+        assert_eq!(max_function_nesting(&parse(code)), 3);
+    }
+
+    #[test]
+    fn test_unused_declarations() {
+        let code = r#"const width = 10
+const spare = 3
+const part001 = startSketchOn('XY')
+fn helper = (x) => {
+  return x * width
+}
+show(helper(2))
+"#;
+        let program = parse(code);
+        let unused: Vec<_> = unused_declarations(&program)
+            .into_iter()
+            .map(|declarator| declarator.id.name.as_str())
+            .collect();
+        assert_eq!(unused, vec!["spare"]);
+    }
+
+    #[test]
+    fn test_comment_density() {
+        let code = r#"fn double = (x) => {
+  // double it
+  return x * 2
+}
+fn triple = (x) => {
+  return x * 3
+}
+"#;
+        let densities = comment_density(&parse(code));
+        assert_eq!(densities.len(), 2);
+        assert!(densities["double"] > 0.1 && densities["double"] < 0.5);
+        assert_eq!(densities["triple"], 0.0);
+    }
+
+    #[test]
+    fn test_names_in_scope() {
+        let code = r#"const width = 10
+const x = 2
+fn area = (x, h) => {
+  const scale = 2
+  return x * h * width * scale
+}
+const after = 1
+"#;
+        let program = parse(code);
+        let names = names_in_scope(&program, code.find("return").unwrap());
+        let found: Vec<_> = names.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(found, vec!["width", "x", "h", "scale"]);
+
+        // The parameter hides the outer `x`.
+        let param_x = code.find("(x").unwrap() + 1;
+        assert_eq!(names[1].1, SourceRange([param_x, param_x + 1]));
+
+        let names = names_in_scope(&program, code.len());
+        let found: Vec<_> = names.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(found, vec!["width", "x", "area", "after"]);
+    }
+
+    #[test]
+    fn test_enclosing_statement() {
+        let code = r#"const width = 10
+fn area = (w, h) => {
+  const scale = 2
+  return w * h * scale
+}
+"#;
+        let program = parse(code);
+        let range_of = |text: &str| {
+            let start = code.find(text).unwrap();
+            SourceRange([start, start + text.len()])
+        };
+
+        // Part of an expression snaps to its statement, at the top level or in a function.
+        let snapped = enclosing_statement(&program, range_of("= 1"));
+        assert_eq!(snapped, Some(range_of("const width = 10")));
+        let snapped = enclosing_statement(&program, range_of("h * s"));
+        assert_eq!(snapped, Some(range_of("return w * h * scale")));
+
+        // Across statements, the body holding both of them.
+        let BodyItem::VariableDeclaration(declaration) = &program.body[1] else {
+            panic!("expected a declaration");
+        };
+        let Value::FunctionExpression(function) = &declaration.declarations[0].init else {
+            panic!("expected a function");
+        };
+        let snapped = enclosing_statement(&program, range_of("2\n  return"));
+        assert_eq!(snapped, Some(SourceRange([function.body.start, function.body.end])));
+        let snapped = enclosing_statement(&program, range_of("10\nfn"));
+        assert_eq!(snapped, Some(SourceRange([program.start, program.end])));
+
+        assert_eq!(enclosing_statement(&program, SourceRange([0, code.len() + 10])), None);
+    }
+
+    #[test]
+    fn test_estimated_recast_len() {
+        let code = r#"const width = 20
+const height = 10
+// the area of a rectangle
+fn area = (w, h) => {
+  const result = w * h
+  return result
+}
+const part001 = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> line([width, 0], %)
+  |> line([0, height], %)
+  |> line([-width, 0], %)
+  |> close(%)
+  |> extrude(5, %)
+const settings = { name: 'bracket', thickness: 0.25, holes: [1, 2, 3] }
+show(part001, area(width, height))
+"#;
+        let program = parse(code);
+        for options in [
+            FormatOptions::default(),
+            FormatOptions {
+                tab_size: 4,
+                ..Default::default()
+            },
+        ] {
+            let actual = program.recast(&options, 0).len() as f64;
+            let estimate = estimated_recast_len(&program, &options) as f64;
+            assert!(
+                (estimate - actual).abs() / actual < 0.05,
+                "estimate {estimate} is too far from the actual length {actual}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_object_shapes() {
+        let code = r#"const a = { x: 1, y: 2 }
+const b = { y: 3, x: 4 }
+const c = { x: 5, y: 6, z: { x: 7 } }
+"#;
+        let shapes = object_shapes(&parse(code));
+        assert_eq!(
+            shapes,
+            vec![
+                vec!["x".to_owned(), "y".to_owned()],
+                vec!["x".to_owned(), "y".to_owned(), "z".to_owned()],
+                vec!["x".to_owned()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_operators_used() {
+        let program = parse("const a = 1 + 2 * 3\nfn f = (x) => {\n  return x * 2 + a\n}\nshow(-a, f(a))");
+        assert_eq!(
+            operators_used(&program),
+            HashSet::from([BinaryOperator::Add, BinaryOperator::Mul])
+        );
+        assert_eq!(unary_operators_used(&program), HashSet::from([UnaryOperator::Neg]));
+    }
+
+    #[test]
+    fn test_call_chains() {
+        let code = r#"const part = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> line([0, 10], %)
+  |> close(%)
+const size = 5 |> double(%)
+"#;
+        assert_eq!(
+            call_chains(&parse(code)),
+            vec![
+                vec![
+                    "startSketchOn".to_owned(),
+                    "startProfileAt".to_owned(),
+                    "line".to_owned(),
+                    "close".to_owned(),
+                ],
+                vec!["double".to_owned()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_call_arg_type_profiles() {
+        let code = r#"const a = pad(1, 'left')
+const b = pad(-2.5, { width: 3 }, true)
+const c = pad(a, [1, 2])
+show(b)
+"#;
+        let profiles = call_arg_type_profiles(&parse(code));
+        assert_eq!(profiles.len(), 2);
+        assert_eq!(
+            profiles["pad"],
+            vec![
+                HashSet::from([ArgType::Number, ArgType::Unknown]),
+                HashSet::from([ArgType::String, ArgType::Object, ArgType::Array]),
+                HashSet::from([ArgType::Bool]),
+            ]
+        );
+        assert_eq!(profiles["show"], vec![HashSet::from([ArgType::Unknown])]);
+    }
+
+    #[test]
+    fn test_call_arg_type_profiles_special_numbers_and_shadowed_bools() {
+        let mut program = parse("fn f = (flag) => {\n  return g(flag, -x)\n}\nshow(true)");
+        // The parser can't bind `true` or write infinity, so edit them in.
+        let BodyItem::VariableDeclaration(declaration) = &mut program.body[0] else {
+            panic!("expected a variable declaration");
+        };
+        let Value::FunctionExpression(function) = &mut declaration.declarations[0].init else {
+            panic!("expected a function expression");
+        };
+        function.params[0].name = "true".into();
+        let BodyItem::ReturnStatement(statement) = &mut function.body.body[0] else {
+            panic!("expected a return statement");
+        };
+        let Value::CallExpression(call) = &mut statement.argument else {
+            panic!("expected a call expression");
+        };
+        let Value::Identifier(flag) = &mut call.arguments[0] else {
+            panic!("expected an identifier");
+        };
+        flag.name = "true".into();
+        let Value::UnaryExpression(negated) = &mut call.arguments[1] else {
+            panic!("expected a unary expression");
+        };
+        negated.argument = BinaryPart::Literal(Box::new(Literal::from_f64(f64::INFINITY)));
+
+        let profiles = call_arg_type_profiles(&program);
+        assert_eq!(
+            profiles["g"],
+            vec![HashSet::from([ArgType::Unknown]), HashSet::from([ArgType::Number])]
+        );
+        // Outside the function, `true` is the boolean again.
+        assert_eq!(profiles["show"], vec![HashSet::from([ArgType::Bool])]);
+    }
+
+    #[test]
+    fn test_structure_fingerprint() {
+        let a = parse("const width = 10\nconst part = startSketchAt([0, 0])\n  |> line([width, 2], %)\nshow(part)");
+        let b = parse("const height = 25\nconst p = startSketchAt([1, 5])\n  |> line([height, 0], %)\nshow(p)");
+        assert_eq!(structure_fingerprint(&a), "const,const,expr|line,show,startSketchAt");
+        assert_eq!(structure_fingerprint(&a), structure_fingerprint(&b));
+
+        let c = parse("const width = 10\nconst part = startSketchAt([0, 0])\n  |> close(%)\nshow(part)");
+        assert_ne!(structure_fingerprint(&a), structure_fingerprint(&c));
+        let d = parse("const width = 10\nshow(width)");
+        assert_ne!(structure_fingerprint(&a), structure_fingerprint(&d));
+    }
+
+    #[test]
+    fn test_longest_pipe_and_deepest_node() {
+        let code = r#"const a = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> close(%)
+const b = startSketchOn('XZ')
+  |> startProfileAt([0, 0], %)
+  |> line([1, 1], %)
+  |> close(%)
+const c = startSketchOn('YZ')
+  |> startProfileAt([0, 0], %)
+  |> line([1, 1], %)
+  |> close(%)
+"#;
+        let program = parse(code);
+        let longest = longest_pipe(&program).unwrap();
+        assert_eq!(longest.body.len(), 4);
+        assert_eq!(longest.start, code.find("startSketchOn('XZ')").unwrap());
+
+        // The literals in `[0, 0]` are inside the program, declaration, declarator, pipe, call
+        // and array. The first of them wins.
+        let (node, depth) = deepest_node(&program).unwrap();
+        assert_eq!(depth, 6);
+        assert!(matches!(node, NodeRef::Literal(_)));
+        assert_eq!(node.start(), code.find("[0, 0]").unwrap() + 1);
+
+        assert!(longest_pipe(&parse("const x = 1")).is_none());
+    }
+
+    #[test]
+    fn test_dependency_graph() {
+        let code = r#"const a = 1
+const b = a + 1
+const c = [b, min(a, 2)]
+fn ping = (n) => {
+  return pong(n - 1)
+}
+fn pong = (n) => {
+  return ping(n)
+}
+"#;
+        let program = parse(code);
+        let graph = dependency_graph(&program);
+        let set = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<HashSet<_>>();
+        assert_eq!(graph.len(), 5);
+        assert_eq!(graph["a"], set(&[]));
+        assert_eq!(graph["b"], set(&["a"]));
+        assert_eq!(graph["c"], set(&["a", "b"]));
+        assert_eq!(graph["ping"], set(&["pong"]));
+        assert_eq!(graph["pong"], set(&["ping"]));
+
+        assert_eq!(cycles(&graph), vec![vec!["ping".to_owned(), "pong".to_owned()]]);
+        assert!(cycles(&dependency_graph(&parse("const a = 1\nconst b = a"))).is_empty());
+    }
+
+    #[test]
+    fn test_diff_summary() {
+        let old = parse("const a = 1\nconst b = 5\nshow(a, b)");
+        let new_src = "const a = 1\n\nconst b = 10\nshow(a, b)";
+        assert_eq!(
+            diff_summary(&old, &parse(new_src), new_src),
+            vec!["changed literal 5→10 at line 3".to_owned()]
+        );
+
+        let new_src = "const a = 1\nconst b = 5\nshow(a * b)\nshow(b)";
+        assert_eq!(
+            diff_summary(&old, &parse(new_src), new_src),
+            vec![
+                "replaced identifier with binary expression at line 3".to_owned(),
+                "removed identifier".to_owned(),
+                "added statement at line 4".to_owned(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1034 @@
+//! A flat representation of the AST.
+//! Instead of a tree of boxed nodes, every expression is stored in one `Vec` and refers to its
+//! children by index. Large programs deserialize into a handful of allocations this way, and
+//! walking them doesn't chase a pointer per node.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    ast::{
+        error::KclAstError,
+        transform::EditError,
+        types::{
+            ArrayExpression, BinaryExpression, BinaryOperator, BinaryPart, BodyItem, CallExpression,
+            ExpressionStatement, Function, FunctionExpression, Identifier, Literal, LiteralIdentifier,
+            MemberExpression, MemberObject, NonCodeMeta, ObjectExpression, ObjectProperty, PipeExpression,
+            PipeSubstitution, Program, ReturnStatement, UnaryExpression, UnaryOperator, Value, VariableDeclaration,
+            VariableDeclarator, VariableKind,
+        },
+    },
+    executor::SourceRange,
+};
+
+/// The index of an expression in an [`ArenaProgram`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct ValueId(usize);
+
+/// The index of a statement list (the program itself, or a function body) in an [`ArenaProgram`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct BodyId(usize);
+
+/// A program stored as flat lists of nodes.
+/// Every id in one built by [`to_arena`] is valid, and an expression only refers to expressions
+/// before it. One that was deserialized may not be, so lookups return `None` for a bad id, and
+/// [`from_arena`] checks both.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArenaProgram {
+    bodies: Vec<ArenaBody>,
+    values: Vec<ArenaValue>,
+}
+
+impl ArenaProgram {
+    /// The top-level statements of the program.
+    pub fn root(&self) -> Option<&ArenaBody> {
+        self.bodies.first()
+    }
+
+    pub fn body(&self, id: BodyId) -> Option<&ArenaBody> {
+        self.bodies.get(id.0)
+    }
+
+    pub fn value(&self, id: ValueId) -> Option<&ArenaValue> {
+        self.values.get(id.0)
+    }
+
+    /// How many expressions the program contains.
+    pub fn value_count(&self) -> usize {
+        self.values.len()
+    }
+}
+
+/// A list of statements, with the comments between them.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArenaBody {
+    pub start: usize,
+    pub end: usize,
+    pub items: Vec<ArenaBodyItem>,
+    pub non_code_meta: NonCodeMeta,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum ArenaBodyItem {
+    ExpressionStatement {
+        start: usize,
+        end: usize,
+        expression: ValueId,
+    },
+    VariableDeclaration {
+        start: usize,
+        end: usize,
+        declarations: Vec<ArenaDeclarator>,
+        kind: VariableKind,
+    },
+    ReturnStatement {
+        start: usize,
+        end: usize,
+        argument: ValueId,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ArenaDeclarator {
+    pub start: usize,
+    pub end: usize,
+    pub id: Identifier,
+    pub init: ValueId,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ArenaProperty {
+    pub start: usize,
+    pub end: usize,
+    pub key: Identifier,
+    pub value: ValueId,
+}
+
+/// An expression. Leaves are stored inline, everything else refers to its children by id.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum ArenaValue {
+    Literal(Literal),
+    Identifier(Identifier),
+    PipeSubstitution(PipeSubstitution),
+    BinaryExpression {
+        start: usize,
+        end: usize,
+        operator: BinaryOperator,
+        left: ValueId,
+        right: ValueId,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parenthesized: Option<bool>,
+    },
+    FunctionExpression {
+        start: usize,
+        end: usize,
+        params: Vec<Identifier>,
+        body: BodyId,
+    },
+    CallExpression {
+        start: usize,
+        end: usize,
+        callee: Identifier,
+        arguments: Vec<ValueId>,
+        optional: bool,
+        function: Function,
+    },
+    PipeExpression {
+        start: usize,
+        end: usize,
+        body: Vec<ValueId>,
+        non_code_meta: NonCodeMeta,
+    },
+    ArrayExpression {
+        start: usize,
+        end: usize,
+        elements: Vec<ValueId>,
+        line_breaks: Option<Vec<usize>>,
+    },
+    ObjectExpression {
+        start: usize,
+        end: usize,
+        properties: Vec<ArenaProperty>,
+        line_breaks: Option<Vec<usize>>,
+    },
+    MemberExpression {
+        start: usize,
+        end: usize,
+        object: ValueId,
+        /// A literal or an identifier.
+        property: ValueId,
+        computed: bool,
+    },
+    UnaryExpression {
+        start: usize,
+        end: usize,
+        operator: UnaryOperator,
+        argument: ValueId,
+    },
+}
+
+/// Why an [`ArenaProgram`] couldn't be rebuilt into a tree. One made by [`to_arena`] always
+/// can be, so these only come from arenas deserialized from somewhere else.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ArenaError {
+    #[error("there is no expression {0:?}")]
+    NoSuchValue(ValueId),
+    #[error("there is no statement list {0:?}")]
+    NoSuchBody(BodyId),
+    /// Children come before their parents, which is what rules out cycles.
+    #[error("expression {parent:?} refers to {child:?}, which does not come before it")]
+    OutOfOrder { parent: ValueId, child: ValueId },
+    #[error("expression {id:?} can't be used as {expected}")]
+    WrongKind { id: ValueId, expected: &'static str },
+}
+
+/// Flatten a program into its arena form.
+pub fn to_arena(program: &Program) -> ArenaProgram {
+    let mut arena = ArenaProgram {
+        bodies: Vec::new(),
+        values: Vec::new(),
+    };
+    push_body(&mut arena, program);
+    arena
+}
+
+/// Rebuild the tree form of a program from its arena form.
+pub fn from_arena(arena: &ArenaProgram) -> Result<Program, KclAstError> {
+    Ok(build_body(arena, BodyId(0), arena.values.len())?)
+}
+
+fn push_body(arena: &mut ArenaProgram, program: &Program) -> BodyId {
+    // Reserve the slot first so the root is always body 0, whatever it contains.
+    let id = BodyId(arena.bodies.len());
+    arena.bodies.push(ArenaBody {
+        start: program.start,
+        end: program.end,
+        items: Vec::new(),
+        non_code_meta: program.non_code_meta.clone(),
+    });
+
+    let items = program
+        .body
+        .iter()
+        .map(|item| match item {
+            BodyItem::ExpressionStatement(statement) => ArenaBodyItem::ExpressionStatement {
+                start: statement.start,
+                end: statement.end,
+                expression: push_value(arena, &statement.expression),
+            },
+            BodyItem::VariableDeclaration(declaration) => ArenaBodyItem::VariableDeclaration {
+                start: declaration.start,
+                end: declaration.end,
+                declarations: declaration
+                    .declarations
+                    .iter()
+                    .map(|declarator| ArenaDeclarator {
+                        start: declarator.start,
+                        end: declarator.end,
+                        id: declarator.id.clone(),
+                        init: push_value(arena, &declarator.init),
+                    })
+                    .collect(),
+                kind: declaration.kind.clone(),
+            },
+            BodyItem::ReturnStatement(statement) => ArenaBodyItem::ReturnStatement {
+                start: statement.start,
+                end: statement.end,
+                argument: push_value(arena, &statement.argument),
+            },
+        })
+        .collect();
+    arena.bodies[id.0].items = items;
+    id
+}
+
+fn push(arena: &mut ArenaProgram, value: ArenaValue) -> ValueId {
+    arena.values.push(value);
+    ValueId(arena.values.len() - 1)
+}
+
+fn push_value(arena: &mut ArenaProgram, value: &Value) -> ValueId {
+    let value = match value {
+        Value::Literal(literal) => ArenaValue::Literal(*literal.clone()),
+        Value::Identifier(identifier) => ArenaValue::Identifier(*identifier.clone()),
+        Value::PipeSubstitution(substitution) => ArenaValue::PipeSubstitution(*substitution.clone()),
+        Value::BinaryExpression(binary) => push_binary(arena, binary),
+        Value::FunctionExpression(function) => ArenaValue::FunctionExpression {
+            start: function.start,
+            end: function.end,
+            params: function.params.clone(),
+            body: push_body(arena, &function.body),
+        },
+        Value::CallExpression(call) => push_call(arena, call),
+        Value::PipeExpression(pipe) => ArenaValue::PipeExpression {
+            start: pipe.start,
+            end: pipe.end,
+            body: pipe.body.iter().map(|value| push_value(arena, value)).collect(),
+            non_code_meta: pipe.non_code_meta.clone(),
+        },
+        Value::ArrayExpression(array) => ArenaValue::ArrayExpression {
+            start: array.start,
+            end: array.end,
+            elements: array.elements.iter().map(|value| push_value(arena, value)).collect(),
+            line_breaks: array.line_breaks.clone(),
+        },
+        Value::ObjectExpression(object) => ArenaValue::ObjectExpression {
+            start: object.start,
+            end: object.end,
+            properties: object
+                .properties
+                .iter()
+                .map(|property| ArenaProperty {
+                    start: property.start,
+                    end: property.end,
+                    key: property.key.clone(),
+                    value: push_value(arena, &property.value),
+                })
+                .collect(),
+            line_breaks: object.line_breaks.clone(),
+        },
+        Value::MemberExpression(member) => push_member(arena, member),
+        Value::UnaryExpression(unary) => push_unary(arena, unary),
+    };
+    push(arena, value)
+}
+
+fn push_part(arena: &mut ArenaProgram, part: &BinaryPart) -> ValueId {
+    let value = match part {
+        BinaryPart::Literal(literal) => ArenaValue::Literal(*literal.clone()),
+        BinaryPart::Identifier(identifier) => ArenaValue::Identifier(*identifier.clone()),
+        BinaryPart::BinaryExpression(binary) => push_binary(arena, binary),
+        BinaryPart::CallExpression(call) => push_call(arena, call),
+        BinaryPart::UnaryExpression(unary) => push_unary(arena, unary),
+        BinaryPart::MemberExpression(member) => push_member(arena, member),
+    };
+    push(arena, value)
+}
+
+fn push_binary(arena: &mut ArenaProgram, binary: &BinaryExpression) -> ArenaValue {
+    ArenaValue::BinaryExpression {
+        start: binary.start,
+        end: binary.end,
+        operator: binary.operator.clone(),
+        left: push_part(arena, &binary.left),
+        right: push_part(arena, &binary.right),
+        parenthesized: binary.parenthesized,
+    }
+}
+
+fn push_unary(arena: &mut ArenaProgram, unary: &UnaryExpression) -> ArenaValue {
+    ArenaValue::UnaryExpression {
+        start: unary.start,
+        end: unary.end,
+        operator: unary.operator.clone(),
+        argument: push_part(arena, &unary.argument),
+    }
+}
+
+fn push_call(arena: &mut ArenaProgram, call: &CallExpression) -> ArenaValue {
+    ArenaValue::CallExpression {
+        start: call.start,
+        end: call.end,
+        callee: call.callee.clone(),
+        arguments: call.arguments.iter().map(|value| push_value(arena, value)).collect(),
+        optional: call.optional,
+        function: call.function.clone(),
+    }
+}
+
+fn push_member(arena: &mut ArenaProgram, member: &MemberExpression) -> ArenaValue {
+    let object = match &member.object {
+        MemberObject::MemberExpression(object) => {
+            let object = push_member(arena, object);
+            push(arena, object)
+        }
+        MemberObject::Identifier(identifier) => push(arena, ArenaValue::Identifier(*identifier.clone())),
+    };
+    let property = match &member.property {
+        LiteralIdentifier::Identifier(identifier) => push(arena, ArenaValue::Identifier(*identifier.clone())),
+        LiteralIdentifier::Literal(literal) => push(arena, ArenaValue::Literal(*literal.clone())),
+    };
+    ArenaValue::MemberExpression {
+        start: member.start,
+        end: member.end,
+        object,
+        property,
+        computed: member.computed,
+    }
+}
+
+/// Rebuild a statement list. Every expression in it has to come before `before`, the id of the
+/// function it's the body of, or the end of the arena for the root.
+fn build_body(arena: &ArenaProgram, id: BodyId, before: usize) -> Result<Program, ArenaError> {
+    let body = arena.body(id).ok_or(ArenaError::NoSuchBody(id))?;
+    Ok(Program {
+        start: body.start,
+        end: body.end,
+        body: body
+            .items
+            .iter()
+            .map(|item| {
+                Ok(match item {
+                    ArenaBodyItem::ExpressionStatement { start, end, expression } => {
+                        BodyItem::ExpressionStatement(ExpressionStatement {
+                            start: *start,
+                            end: *end,
+                            expression: build_value(arena, *expression, before)?,
+                        })
+                    }
+                    ArenaBodyItem::VariableDeclaration {
+                        start,
+                        end,
+                        declarations,
+                        kind,
+                    } => BodyItem::VariableDeclaration(VariableDeclaration {
+                        start: *start,
+                        end: *end,
+                        declarations: declarations
+                            .iter()
+                            .map(|declarator| {
+                                Ok(VariableDeclarator {
+                                    start: declarator.start,
+                                    end: declarator.end,
+                                    id: declarator.id.clone(),
+                                    init: build_value(arena, declarator.init, before)?,
+                                })
+                            })
+                            .collect::<Result<_, ArenaError>>()?,
+                        kind: kind.clone(),
+                    }),
+                    ArenaBodyItem::ReturnStatement { start, end, argument } => {
+                        BodyItem::ReturnStatement(ReturnStatement {
+                            start: *start,
+                            end: *end,
+                            argument: build_value(arena, *argument, before)?,
+                        })
+                    }
+                })
+            })
+            .collect::<Result<_, ArenaError>>()?,
+        non_code_meta: body.non_code_meta.clone(),
+    })
+}
+
+/// Look up an expression that something before `before` refers to.
+fn checked_value(arena: &ArenaProgram, id: ValueId, before: usize) -> Result<&ArenaValue, ArenaError> {
+    let value = arena.value(id).ok_or(ArenaError::NoSuchValue(id))?;
+    if id.0 >= before {
+        return Err(ArenaError::OutOfOrder {
+            parent: ValueId(before),
+            child: id,
+        });
+    }
+    Ok(value)
+}
+
+fn build_value(arena: &ArenaProgram, id: ValueId, before: usize) -> Result<Value, ArenaError> {
+    let build_values = |ids: &[ValueId]| {
+        ids.iter()
+            .map(|child| build_value(arena, *child, id.0))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    Ok(match checked_value(arena, id, before)? {
+        ArenaValue::Literal(literal) => Value::Literal(Box::new(literal.clone())),
+        ArenaValue::Identifier(identifier) => Value::Identifier(Box::new(identifier.clone())),
+        ArenaValue::PipeSubstitution(substitution) => Value::PipeSubstitution(Box::new(substitution.clone())),
+        ArenaValue::BinaryExpression {
+            start,
+            end,
+            operator,
+            left,
+            right,
+            parenthesized,
+        } => Value::BinaryExpression(Box::new(BinaryExpression {
+            start: *start,
+            end: *end,
+            operator: operator.clone(),
+            left: build_part(arena, *left, id.0)?,
+            right: build_part(arena, *right, id.0)?,
+            parenthesized: *parenthesized,
+        })),
+        ArenaValue::FunctionExpression {
+            start,
+            end,
+            params,
+            body,
+        } => Value::FunctionExpression(Box::new(FunctionExpression {
+            start: *start,
+            end: *end,
+            params: params.clone(),
+            body: build_body(arena, *body, id.0)?,
+        })),
+        ArenaValue::CallExpression {
+            start,
+            end,
+            callee,
+            arguments,
+            optional,
+            function,
+        } => Value::CallExpression(Box::new(CallExpression {
+            start: *start,
+            end: *end,
+            callee: callee.clone(),
+            arguments: build_values(arguments)?,
+            optional: *optional,
+            function: function.clone(),
+        })),
+        ArenaValue::PipeExpression {
+            start,
+            end,
+            body,
+            non_code_meta,
+        } => Value::PipeExpression(Box::new(PipeExpression {
+            start: *start,
+            end: *end,
+            body: build_values(body)?,
+            non_code_meta: non_code_meta.clone(),
+        })),
+        ArenaValue::ArrayExpression {
+            start,
+            end,
+            elements,
+            line_breaks,
+        } => Value::ArrayExpression(Box::new(ArrayExpression {
+            start: *start,
+            end: *end,
+            elements: build_values(elements)?,
+            line_breaks: line_breaks.clone(),
+        })),
+        ArenaValue::ObjectExpression {
+            start,
+            end,
+            properties,
+            line_breaks,
+        } => Value::ObjectExpression(Box::new(ObjectExpression {
+            start: *start,
+            end: *end,
+            properties: properties
+                .iter()
+                .map(|property| {
+                    Ok(ObjectProperty {
+                        start: property.start,
+                        end: property.end,
+                        key: property.key.clone(),
+                        value: build_value(arena, property.value, id.0)?,
+                    })
+                })
+                .collect::<Result<_, ArenaError>>()?,
+            line_breaks: line_breaks.clone(),
+        })),
+        ArenaValue::MemberExpression {
+            start,
+            end,
+            object,
+            property,
+            computed,
+        } => Value::MemberExpression(Box::new(MemberExpression {
+            start: *start,
+            end: *end,
+            object: match build_value(arena, *object, id.0)? {
+                Value::MemberExpression(member) => MemberObject::MemberExpression(member),
+                Value::Identifier(identifier) => MemberObject::Identifier(identifier),
+                _ => {
+                    return Err(ArenaError::WrongKind {
+                        id: *object,
+                        expected: "a member object",
+                    })
+                }
+            },
+            property: match checked_value(arena, *property, id.0)? {
+                ArenaValue::Identifier(identifier) => LiteralIdentifier::Identifier(Box::new(identifier.clone())),
+                ArenaValue::Literal(literal) => LiteralIdentifier::Literal(Box::new(literal.clone())),
+                _ => {
+                    return Err(ArenaError::WrongKind {
+                        id: *property,
+                        expected: "a member property",
+                    })
+                }
+            },
+            computed: *computed,
+        })),
+        ArenaValue::UnaryExpression {
+            start,
+            end,
+            operator,
+            argument,
+        } => Value::UnaryExpression(Box::new(UnaryExpression {
+            start: *start,
+            end: *end,
+            operator: operator.clone(),
+            argument: build_part(arena, *argument, id.0)?,
+        })),
+    })
+}
+
+fn build_part(arena: &ArenaProgram, id: ValueId, before: usize) -> Result<BinaryPart, ArenaError> {
+    Ok(match build_value(arena, id, before)? {
+        Value::Literal(literal) => BinaryPart::Literal(literal),
+        Value::Identifier(identifier) => BinaryPart::Identifier(identifier),
+        Value::BinaryExpression(binary) => BinaryPart::BinaryExpression(binary),
+        Value::CallExpression(call) => BinaryPart::CallExpression(call),
+        Value::UnaryExpression(unary) => BinaryPart::UnaryExpression(unary),
+        Value::MemberExpression(member) => BinaryPart::MemberExpression(member),
+        _ => {
+            return Err(ArenaError::WrongKind {
+                id,
+                expected: "an operand",
+            })
+        }
+    })
+}
+
+/// Where an expression sits in an [`ArenaProgram`].
+#[derive(Debug, Clone, Copy)]
+enum Parent {
+    Value(ValueId),
+    Body(BodyId),
+}
+
+/// An [`ArenaProgram`] that keeps a structural hash of every expression and statement list.
+/// The hashes ignore source positions and comments, so two nodes hash the same when they
+/// recast to the same code. Its edits ([`HashedProgram::set_literal`],
+/// [`HashedProgram::set_binary_operator`] and [`HashedProgram::move_object_property`]) only
+/// rehash the expression they change and its ancestors, which keeps the hash cheap to maintain
+/// while a large file is edited. After any other change to the program, the `HashedProgram` has
+/// to be made again with [`HashedProgram::new`].
+#[derive(Debug, Clone)]
+pub struct HashedProgram {
+    arena: ArenaProgram,
+    value_hashes: Vec<u64>,
+    body_hashes: Vec<u64>,
+    value_parents: Vec<Option<Parent>>,
+    /// The function expression each body belongs to, `None` for the root.
+    body_parents: Vec<Option<ValueId>>,
+}
+
+impl HashedProgram {
+    pub fn new(program: &Program) -> Self {
+        let arena = to_arena(program);
+        let mut hashed = Self {
+            value_hashes: vec![0; arena.values.len()],
+            body_hashes: vec![0; arena.bodies.len()],
+            value_parents: vec![None; arena.values.len()],
+            body_parents: vec![None; arena.bodies.len()],
+            arena,
+        };
+        hashed.fill_body(BodyId(0));
+        hashed
+    }
+
+    pub fn arena(&self) -> &ArenaProgram {
+        &self.arena
+    }
+
+    /// The hash of the whole program.
+    pub fn hash(&self) -> u64 {
+        self.body_hashes[0]
+    }
+
+    pub fn value_hash(&self, id: ValueId) -> Option<u64> {
+        self.value_hashes.get(id.0).copied()
+    }
+
+    pub fn body_hash(&self, id: BodyId) -> Option<u64> {
+        self.body_hashes.get(id.0).copied()
+    }
+
+    /// The innermost expression spanning exactly `source_range`.
+    pub fn value_at(&self, source_range: SourceRange) -> Option<ValueId> {
+        // Children come before their parents, so the first match is the innermost.
+        self.value_where(|value| value.source_range() == source_range)
+    }
+
+    /// Replace a literal, rehashing it and everything that contains it.
+    pub fn set_literal(&mut self, id: ValueId, literal: Literal) -> Result<(), KclAstError> {
+        let Some(ArenaValue::Literal(old)) = self.arena.values.get_mut(id.0) else {
+            return Err(EditError::NotALiteral(id).into());
+        };
+        *old = literal;
+        self.rehash_from(id);
+        Ok(())
+    }
+
+    /// Change the operator of the binary expression spanning exactly `source_range`, like
+    /// [`transform::set_binary_operator`](crate::ast::transform::set_binary_operator), rehashing
+    /// it and everything that contains it.
+    pub fn set_binary_operator(
+        &mut self,
+        source_range: SourceRange,
+        operator: BinaryOperator,
+    ) -> Result<(), KclAstError> {
+        let id = self
+            .value_where(|value| {
+                matches!(value, ArenaValue::BinaryExpression { .. }) && value.source_range() == source_range
+            })
+            .ok_or(EditError::NotABinaryExpression(source_range))?;
+        if let ArenaValue::BinaryExpression { operator: old, .. } = &mut self.arena.values[id.0] {
+            *old = operator;
+        }
+        self.rehash_from(id);
+        Ok(())
+    }
+
+    /// Move the property at index `from` of the object spanning exactly `object_range` to index
+    /// `to`, like [`transform::move_object_property`](crate::ast::transform::move_object_property),
+    /// rehashing the object and everything that contains it.
+    pub fn move_object_property(
+        &mut self,
+        object_range: SourceRange,
+        from: usize,
+        to: usize,
+    ) -> Result<(), KclAstError> {
+        let id = self
+            .value_where(|value| {
+                matches!(value, ArenaValue::ObjectExpression { .. }) && value.source_range() == object_range
+            })
+            .ok_or(EditError::NotAnObject(object_range))?;
+        if let ArenaValue::ObjectExpression { properties, .. } = &mut self.arena.values[id.0] {
+            let len = properties.len();
+            if let Some(index) = [from, to].into_iter().find(|index| *index >= len) {
+                return Err(EditError::NoSuchProperty { index, len }.into());
+            }
+            let property = properties.remove(from);
+            properties.insert(to, property);
+        }
+        self.rehash_from(id);
+        Ok(())
+    }
+
+    /// The first expression `f` is true of.
+    fn value_where(&self, f: impl Fn(&ArenaValue) -> bool) -> Option<ValueId> {
+        self.arena.values.iter().position(f).map(ValueId)
+    }
+
+    /// Rehash an expression that changed, then each of its ancestors.
+    fn rehash_from(&mut self, id: ValueId) {
+        self.value_hashes[id.0] = self.hash_value(id);
+        let mut parent = self.value_parents[id.0];
+        while let Some(next) = parent {
+            parent = match next {
+                Parent::Value(id) => {
+                    self.value_hashes[id.0] = self.hash_value(id);
+                    self.value_parents[id.0]
+                }
+                Parent::Body(id) => {
+                    self.body_hashes[id.0] = self.hash_body(id);
+                    self.body_parents[id.0].map(Parent::Value)
+                }
+            };
+        }
+    }
+
+    /// Hash a body and everything in it, recording who contains what.
+    fn fill_body(&mut self, id: BodyId) {
+        let children: Vec<ValueId> = self.arena.bodies[id.0].items.iter().flat_map(item_values).collect();
+        for child in children {
+            self.value_parents[child.0] = Some(Parent::Body(id));
+            self.fill_value(child);
+        }
+        self.body_hashes[id.0] = self.hash_body(id);
+    }
+
+    fn fill_value(&mut self, id: ValueId) {
+        if let ArenaValue::FunctionExpression { body, .. } = self.arena.values[id.0] {
+            self.body_parents[body.0] = Some(id);
+            self.fill_body(body);
+        }
+        for child in self.arena.values[id.0].child_values() {
+            self.value_parents[child.0] = Some(Parent::Value(id));
+            self.fill_value(child);
+        }
+        self.value_hashes[id.0] = self.hash_value(id);
+    }
+
+    /// Hash a body from the cached hashes of its expressions.
+    fn hash_body(&self, id: BodyId) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for item in &self.arena.bodies[id.0].items {
+            match item {
+                ArenaBodyItem::ExpressionStatement { expression, .. } => {
+                    "ExpressionStatement".hash(&mut hasher);
+                    self.value_hashes[expression.0].hash(&mut hasher);
+                }
+                ArenaBodyItem::VariableDeclaration { declarations, kind, .. } => {
+                    "VariableDeclaration".hash(&mut hasher);
+                    kind.to_string().hash(&mut hasher);
+                    for declarator in declarations {
+                        declarator.id.name.hash(&mut hasher);
+                        self.value_hashes[declarator.init.0].hash(&mut hasher);
+                    }
+                }
+                ArenaBodyItem::ReturnStatement { argument, .. } => {
+                    "ReturnStatement".hash(&mut hasher);
+                    self.value_hashes[argument.0].hash(&mut hasher);
+                }
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Hash an expression from the cached hashes of its children.
+    fn hash_value(&self, id: ValueId) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let value = &self.arena.values[id.0];
+        match value {
+            ArenaValue::Literal(literal) => {
+                "Literal".hash(&mut hasher);
+                literal.value.to_string().hash(&mut hasher);
+            }
+            ArenaValue::Identifier(identifier) => {
+                "Identifier".hash(&mut hasher);
+                identifier.name.hash(&mut hasher);
+            }
+            ArenaValue::PipeSubstitution(_) => "PipeSubstitution".hash(&mut hasher),
+            ArenaValue::BinaryExpression { operator, .. } => {
+                "BinaryExpression".hash(&mut hasher);
+                operator.hash(&mut hasher);
+            }
+            ArenaValue::FunctionExpression { params, body, .. } => {
+                "FunctionExpression".hash(&mut hasher);
+                params.iter().for_each(|param| param.name.hash(&mut hasher));
+                self.body_hashes[body.0].hash(&mut hasher);
+            }
+            ArenaValue::CallExpression { callee, optional, .. } => {
+                "CallExpression".hash(&mut hasher);
+                callee.name.hash(&mut hasher);
+                optional.hash(&mut hasher);
+            }
+            ArenaValue::PipeExpression { .. } => "PipeExpression".hash(&mut hasher),
+            ArenaValue::ArrayExpression { .. } => "ArrayExpression".hash(&mut hasher),
+            ArenaValue::ObjectExpression { properties, .. } => {
+                "ObjectExpression".hash(&mut hasher);
+                properties
+                    .iter()
+                    .for_each(|property| property.key.name.hash(&mut hasher));
+            }
+            ArenaValue::MemberExpression { computed, .. } => {
+                "MemberExpression".hash(&mut hasher);
+                computed.hash(&mut hasher);
+            }
+            ArenaValue::UnaryExpression { operator, .. } => {
+                "UnaryExpression".hash(&mut hasher);
+                operator.hash(&mut hasher);
+            }
+        }
+        for child in value.child_values() {
+            self.value_hashes[child.0].hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+impl ArenaValue {
+    /// Where the expression is in the source.
+    pub fn source_range(&self) -> SourceRange {
+        let (start, end) = match self {
+            ArenaValue::Literal(literal) => (literal.start, literal.end),
+            ArenaValue::Identifier(identifier) => (identifier.start, identifier.end),
+            ArenaValue::PipeSubstitution(substitution) => (substitution.start, substitution.end),
+            ArenaValue::BinaryExpression { start, end, .. }
+            | ArenaValue::FunctionExpression { start, end, .. }
+            | ArenaValue::CallExpression { start, end, .. }
+            | ArenaValue::PipeExpression { start, end, .. }
+            | ArenaValue::ArrayExpression { start, end, .. }
+            | ArenaValue::ObjectExpression { start, end, .. }
+            | ArenaValue::MemberExpression { start, end, .. }
+            | ArenaValue::UnaryExpression { start, end, .. } => (*start, *end),
+        };
+        SourceRange([start, end])
+    }
+
+    /// The expressions this one refers to directly, in source order. A function's body is a
+    /// statement list rather than an expression, so it isn't included.
+    fn child_values(&self) -> Vec<ValueId> {
+        match self {
+            ArenaValue::Literal(_)
+            | ArenaValue::Identifier(_)
+            | ArenaValue::PipeSubstitution(_)
+            | ArenaValue::FunctionExpression { .. } => Vec::new(),
+            ArenaValue::BinaryExpression { left, right, .. } => vec![*left, *right],
+            ArenaValue::CallExpression { arguments, .. } => arguments.clone(),
+            ArenaValue::PipeExpression { body, .. } => body.clone(),
+            ArenaValue::ArrayExpression { elements, .. } => elements.clone(),
+            ArenaValue::ObjectExpression { properties, .. } => {
+                properties.iter().map(|property| property.value).collect()
+            }
+            ArenaValue::MemberExpression { object, property, .. } => vec![*object, *property],
+            ArenaValue::UnaryExpression { argument, .. } => vec![*argument],
+        }
+    }
+}
+
+/// The expressions a statement holds directly.
+fn item_values(item: &ArenaBodyItem) -> Vec<ValueId> {
+    match item {
+        ArenaBodyItem::ExpressionStatement { expression, .. } => vec![*expression],
+        ArenaBodyItem::VariableDeclaration { declarations, .. } => {
+            declarations.iter().map(|declarator| declarator.init).collect()
+        }
+        ArenaBodyItem::ReturnStatement { argument, .. } => vec![*argument],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::walk::ast_eq;
+
+    #[test]
+    fn test_arena_round_trip() {
+        let code = r#"const part001 = startSketchOn('XY')
+  |> startProfileAt([0, 0], %) // start here
+  |> line([0, -width / 2], %)
+  |> close(%)
+fn area = (w, h) => {
+  const obj = { w: w, h: h }
+  return obj.w * obj['h']
+}
+const x = area(3, 4) + pos[0]
+"#;
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+
+        let arena = to_arena(&program);
+        assert_eq!(arena.root().unwrap().items.len(), 3);
+
+        // It also survives serialization.
+        let json = serde_json::to_string(&arena).unwrap();
+        let arena: ArenaProgram = serde_json::from_str(&json).unwrap();
+
+        let rebuilt = from_arena(&arena).unwrap();
+        assert!(ast_eq(&program, &rebuilt));
+        assert_eq!(program, rebuilt);
+    }
+
+    #[test]
+    fn test_from_arena_malformed() {
+        let program = crate::parser::Parser::new(crate::token::lexer("const x = 1 + 2"))
+            .ast()
+            .unwrap();
+        let json = serde_json::to_value(to_arena(&program)).unwrap();
+        assert_eq!(json["values"][2]["type"], "BinaryExpression");
+        let rebuild = |json: serde_json::Value| from_arena(&serde_json::from_value(json).unwrap());
+
+        // `1 + 2` as its own operand.
+        let mut cycle = json.clone();
+        cycle["values"][2]["left"] = 2.into();
+        assert_eq!(
+            rebuild(cycle),
+            Err(ArenaError::OutOfOrder {
+                parent: ValueId(2),
+                child: ValueId(2)
+            }
+            .into())
+        );
+
+        let mut missing = json.clone();
+        missing["values"][2]["right"] = 9.into();
+        assert_eq!(rebuild(missing), Err(ArenaError::NoSuchValue(ValueId(9)).into()));
+
+        let mut no_root = json.clone();
+        no_root["bodies"] = serde_json::json!([]);
+        assert_eq!(rebuild(no_root), Err(ArenaError::NoSuchBody(BodyId(0)).into()));
+
+        // `1` swapped for a `%`, which can't be an operand.
+        let mut wrong = json;
+        wrong["values"][0] = serde_json::json!({ "type": "PipeSubstitution", "start": 10, "end": 11 });
+        assert_eq!(
+            rebuild(wrong),
+            Err(ArenaError::WrongKind {
+                id: ValueId(0),
+                expected: "an operand"
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn test_hashed_program_rehashes_ancestors_only() {
+        let parse = |code: &str| crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+        let code = "const a = 1 + 2\nconst b = 3";
+        let mut hashed = HashedProgram::new(&parse(code));
+        let at = |text: &str| {
+            let start = code.find(text).unwrap();
+            hashed.value_at(SourceRange([start, start + text.len()])).unwrap()
+        };
+        let ids = [at("1"), at("2"), at("1 + 2"), at("3")];
+        let before: Vec<Option<u64>> = ids.iter().map(|id| hashed.value_hash(*id)).collect();
+        let program_before = hashed.hash();
+
+        let Some(ArenaValue::Literal(one)) = hashed.arena().value(ids[0]) else {
+            panic!("expected a literal");
+        };
+        let five = Literal {
+            value: 5.into(),
+            raw: "5".to_owned(),
+            ..one.clone()
+        };
+        hashed.set_literal(ids[0], five).unwrap();
+
+        assert_ne!(hashed.value_hash(ids[0]), before[0]);
+        assert_eq!(hashed.value_hash(ids[1]), before[1]);
+        assert_ne!(hashed.value_hash(ids[2]), before[2]);
+        assert_eq!(hashed.value_hash(ids[3]), before[3]);
+        assert_ne!(hashed.hash(), program_before);
+
+        // The cached hashes match hashing the edited program from scratch, wherever it sits.
+        assert_eq!(
+            hashed.hash(),
+            HashedProgram::new(&parse("const a = 5 + 2\n\nconst b = 3")).hash()
+        );
+
+        // `1 + 2` isn't a literal.
+        assert_eq!(
+            hashed.set_literal(ids[2], Literal::from_f64(1.0)),
+            Err(EditError::NotALiteral(ids[2]).into())
+        );
+        assert_eq!(hashed.value_hash(ValueId(hashed.arena().value_count())), None);
+    }
+
+    #[test]
+    fn test_hashed_program_edits() {
+        let parse = |code: &str| crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+        let code = "const a = 1 + 2\nconst b = { x: 1, y: 2 }";
+        let mut hashed = HashedProgram::new(&parse(code));
+        let range = |text: &str| {
+            let start = code.find(text).unwrap();
+            SourceRange([start, start + text.len()])
+        };
+        let object = hashed.value_at(range("{ x: 1, y: 2 }")).unwrap();
+        let object_before = hashed.value_hash(object);
+
+        hashed.set_binary_operator(range("1 + 2"), BinaryOperator::Mul).unwrap();
+        assert_eq!(hashed.value_hash(object), object_before);
+        assert_eq!(
+            hashed.hash(),
+            HashedProgram::new(&parse("const a = 1 * 2\nconst b = { x: 1, y: 2 }")).hash()
+        );
+
+        hashed.move_object_property(range("{ x: 1, y: 2 }"), 1, 0).unwrap();
+        assert_ne!(hashed.value_hash(object), object_before);
+        assert_eq!(
+            hashed.hash(),
+            HashedProgram::new(&parse("const a = 1 * 2\nconst b = { y: 2, x: 1 }")).hash()
+        );
+
+        assert_eq!(
+            hashed.set_binary_operator(range("1"), BinaryOperator::Add),
+            Err(EditError::NotABinaryExpression(range("1")).into())
+        );
+        assert_eq!(
+            hashed.move_object_property(range("{ x: 1, y: 2 }"), 0, 2),
+            Err(EditError::NoSuchProperty { index: 2, len: 2 }.into())
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Everything an editor wants to show about a source file, in one pass.
+//! Parsing carries on past syntax errors so the rest of the file still gets an AST and lints.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ast::{
+        analysis,
+        error::KclAstError,
+        transform,
+        types::Program,
+        walk::{self, NodeRef},
+    },
+    executor::SourceRange,
+};
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, ts_rs::TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum Severity {
+    /// The program can't be parsed as written.
+    Error,
+    /// The program is valid, but probably not what the user meant.
+    Warning,
+}
+
+/// A problem found in a source file.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, ts_rs::TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub source_range: SourceRange,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Converts byte offsets in a source file to lines and columns.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, ts_rs::TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceMap {
+    /// The offset each line starts at.
+    line_starts: Vec<usize>,
+}
+
+impl SourceMap {
+    pub fn new(src: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(src.match_indices('\n').map(|(i, _)| i + 1));
+        Self { line_starts }
+    }
+
+    /// The zero-indexed line and byte column of an offset.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        (line, offset - self.line_starts[line])
+    }
+}
+
+/// The result of [`analyze`].
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, ts_rs::TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct AnalysisResult {
+    /// Everything that could be parsed. Statements with syntax errors are left out.
+    pub program: Program,
+    pub source_map: SourceMap,
+    /// Syntax errors and lint warnings, ordered by where they are in the source.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Parse a source file, recovering from syntax errors, and lint whatever could be parsed.
+/// When parsing fails, the lines holding the error are blanked out (so every other offset
+/// stays where it was) and the file is parsed again, until it parses.
+pub fn analyze(src: &str) -> AnalysisResult {
+    let mut diagnostics = Vec::new();
+    let mut text = src.to_owned();
+    let program = loop {
+        if text.trim().is_empty() {
+            break empty_program(src);
+        }
+        let error = match crate::parser::Parser::new(crate::token::lexer(&text)).ast() {
+            Ok(program) => break program,
+            Err(error) => error,
+        };
+        let source_range = error.source_ranges().first().copied();
+        diagnostics.push(Diagnostic {
+            source_range: source_range.unwrap_or_default(),
+            severity: Severity::Error,
+            message: error.message().to_owned(),
+        });
+        match source_range {
+            Some(source_range) if blank_lines(&mut text, source_range) => {}
+            // There's nothing left to cut out that would make progress.
+            _ => break empty_program(src),
+        }
+    };
+
+    if let Err(KclAstError::Invalid(errors)) = validate(&program) {
+        diagnostics.extend(errors);
+    }
+    diagnostics.extend(lint(&program));
+    diagnostics.sort_by_key(|diagnostic| diagnostic.source_range.0);
+    AnalysisResult {
+        program,
+        source_map: SourceMap::new(src),
+        diagnostics,
+    }
+}
+
+/// Check that a program that parsed can run, returning [`KclAstError::Invalid`] with every
+/// error found if it can't.
+pub fn validate(program: &Program) -> Result<(), KclAstError> {
+    validate_pipe_substitution_placement(program)
+}
+
+/// Check that every pipe substitution (`%`) is inside a stage of a pipe expression after its
+/// first. Anywhere else, there is nothing for it to stand for. The first stage is what the pipe
+/// starts from, so a `%` there is misplaced too.
+pub fn validate_pipe_substitution_placement(program: &Program) -> Result<(), KclAstError> {
+    fn visit(node: NodeRef, misplaced: &mut Vec<SourceRange>) {
+        match node {
+            NodeRef::PipeExpression(pipe) => {
+                if let Some(first) = pipe.body.first() {
+                    visit(first.into(), misplaced);
+                }
+            }
+            NodeRef::PipeSubstitution(substitution) => misplaced.push(substitution.into()),
+            _ => node.for_each_child(|child| visit(child, misplaced)),
+        }
+    }
+
+    let mut misplaced = Vec::new();
+    visit(NodeRef::Program(program), &mut misplaced);
+    if misplaced.is_empty() {
+        return Ok(());
+    }
+    Err(KclAstError::Invalid(
+        misplaced
+            .into_iter()
+            .map(|source_range| Diagnostic {
+                source_range,
+                severity: Severity::Error,
+                message: "`%` can only be used in a stage of a pipe expression after the first".to_owned(),
+            })
+            .collect(),
+    ))
+}
+
+/// Warnings about a program that parsed.
+pub fn lint(program: &Program) -> Vec<Diagnostic> {
+    let unused = analysis::unused_declarations(program)
+        .into_iter()
+        .map(|declarator| {
+            let message = format!("`{}` is declared but never used", declarator.id.name);
+            (SourceRange::from(&declarator.id), message)
+        });
+    let redundant_pipes = redundant_single_stage_pipes(program)
+        .into_iter()
+        .map(|source_range| {
+            let message = "this pipe has only one stage, so it can be written as a call".to_owned();
+            (source_range, message)
+        });
+    unused
+        .chain(precision_loss_warnings(program))
+        .chain(redundant_pipes)
+        .map(|(source_range, message)| Diagnostic {
+            source_range,
+            severity: Severity::Warning,
+            message,
+        })
+        .collect()
+}
+
+/// Integer literals that can't be stored exactly as an `f64`, which is what every number
+/// becomes when the program runs. Above 2^53 not every integer has an `f64`, so for example
+/// 9007199254740993 silently becomes 9007199254740992.
+pub fn precision_loss_warnings(program: &Program) -> Vec<(SourceRange, String)> {
+    let mut warnings = Vec::new();
+    walk::walk(program, |node| {
+        let NodeRef::Literal(literal) = node else {
+            return;
+        };
+        if !literal.value.is_number() || !literal.raw.bytes().all(|b| b.is_ascii_digit()) {
+            return;
+        }
+        let Ok(float) = literal.raw.parse::<f64>() else {
+            return;
+        };
+        // Formatting with a precision prints the exact value of the float.
+        let rounded = format!("{:.0}", float);
+        let digits = match literal.raw.trim_start_matches('0') {
+            "" => "0",
+            digits => digits,
+        };
+        if rounded != digits {
+            warnings.push((
+                literal.into(),
+                format!("{} is too large to be exact, it will be rounded to {}", literal.raw, rounded),
+            ));
+        }
+    });
+    warnings
+}
+
+/// Pipes of a value and a single call, like `x |> f(%)`, which would read better as the call
+/// on its own, `f(x)`. [`transform::simplify_single_stage_pipes`] rewrites them.
+pub fn redundant_single_stage_pipes(program: &Program) -> Vec<SourceRange> {
+    let mut pipes = Vec::new();
+    walk::walk(program, |node| {
+        if let NodeRef::PipeExpression(pipe) = node {
+            if transform::is_redundant_pipe(pipe) {
+                pipes.push(pipe.into());
+            }
+        }
+    });
+    pipes
+}
+
+fn empty_program(src: &str) -> Program {
+    Program {
+        start: 0,
+        end: src.len(),
+        body: vec![],
+        non_code_meta: Default::default(),
+    }
+}
+
+/// Replace the lines overlapping `source_range` with spaces, keeping their byte lengths and
+/// line breaks. Returns false if they were already blank.
+fn blank_lines(text: &mut String, source_range: SourceRange) -> bool {
+    let start = source_range.start().min(text.len());
+    let end = source_range.end().clamp(start, text.len());
+    let line_start = text[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = text[end..].find('\n').map(|i| end + i).unwrap_or(text.len());
+
+    let lines = &text[line_start..line_end];
+    if lines.trim().is_empty() {
+        return false;
+    }
+    let blanked: String = lines
+        .chars()
+        .map(|c| if c == '\n' { "\n".to_owned() } else { " ".repeat(c.len_utf8()) })
+        .collect();
+    text.replace_range(line_start..line_end, &blanked);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_recovers_and_lints() {
+        let code = "const unused = 5\nconst y = )\nshow(1)";
+        let result = analyze(code);
+
+        assert_eq!(result.diagnostics.len(), 2);
+        let warning = &result.diagnostics[0];
+        assert_eq!(warning.severity, Severity::Warning);
+        assert_eq!(warning.source_range, SourceRange([6, 12]));
+        let error = &result.diagnostics[1];
+        assert_eq!(error.severity, Severity::Error);
+        assert_eq!(result.source_map.line_col(error.source_range.start()), (1, 10));
+
+        // Everything but the broken declaration is still there.
+        assert_eq!(result.program.body.len(), 2);
+        assert_eq!(
+            result.program.recast(&Default::default(), 0),
+            "const unused = 5\nshow(1)\n"
+        );
+    }
+
+    #[test]
+    fn test_analyze_clean_program() {
+        let result = analyze("const x = 5\nshow(x)");
+        assert!(result.diagnostics.is_empty());
+        assert_eq!(result.program.body.len(), 2);
+    }
+
+    #[test]
+    fn test_precision_loss_warnings() {
+        let parse = |code: &str| crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+
+        let program = parse("const big = 9007199254740993\nconst exact = 9007199254740992\nshow(big + exact + 100)");
+        let warnings = precision_loss_warnings(&program);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].0, SourceRange([12, 28]));
+        assert_eq!(
+            warnings[0].1,
+            "9007199254740993 is too large to be exact, it will be rounded to 9007199254740992"
+        );
+
+        assert!(precision_loss_warnings(&parse("show(100)")).is_empty());
+    }
+
+    #[test]
+    fn test_validate_pipe_substitution_placement() {
+        let parse = |code: &str| crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+
+        let misplaced = |code: &str| match validate_pipe_substitution_placement(&parse(code)) {
+            Ok(()) => Vec::new(),
+            Err(KclAstError::Invalid(diagnostics)) => diagnostics,
+            Err(err) => panic!("unexpected error: {err}"),
+        };
+
+        assert!(misplaced("const part = startSketchAt([0, 0])\n  |> line([1, 1], %)").is_empty());
+
+        let diagnostics = misplaced("const x = 5\nconst y = min(%, x)");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].source_range, SourceRange([26, 27]));
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(
+            validate(&parse("const x = 5\nconst y = min(%, x)")),
+            Err(KclAstError::Invalid(diagnostics))
+        );
+
+        // The first stage of a pipe comes before there's anything for `%` to stand for.
+        assert_eq!(misplaced("const x = % |> f(%)")[0].source_range, SourceRange([10, 11]));
+        assert_eq!(misplaced("foo(%) |> bar(%)")[0].source_range, SourceRange([4, 5]));
+    }
+
+    #[test]
+    fn test_redundant_single_stage_pipes() {
+        let parse = |code: &str| crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+
+        let code = r#"const x = 5 |> double(%)
+const y = startSketchAt([0, 0])
+  |> line([1, 1], %)
+  |> close(%)
+show(x, y |> g())"#;
+        let program = parse(code);
+        // A pipe with more stages is fine, and `g()` doesn't use `%`, so it isn't the same as a call.
+        assert_eq!(redundant_single_stage_pipes(&program), vec![SourceRange([10, 24])]);
+        let warnings: Vec<_> = lint(&program)
+            .into_iter()
+            .filter(|diagnostic| diagnostic.source_range == SourceRange([10, 24]))
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, Severity::Warning);
+    }
+}
@@ -0,0 +1,117 @@
+//! One error type for everything in `ast` that can fail, so callers have a single thing to
+//! match on. Each category keeps the more specific error it came from.
+
+use thiserror::Error;
+
+use crate::{
+    ast::{
+        arena::ArenaError,
+        diagnostics::Diagnostic,
+        limits::LimitError,
+        transform::{EditError, RefactorError},
+    },
+    errors::KclError,
+    executor::SourceRange,
+};
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum KclAstError {
+    /// The source couldn't be parsed.
+    #[error("{message}")]
+    Parse { source_range: SourceRange, message: String },
+    /// An AST couldn't be converted to or from JSON.
+    #[error("{0}")]
+    Serialization(String),
+    /// A refactoring couldn't be applied.
+    #[error(transparent)]
+    Refactor(#[from] RefactorError),
+    /// An edit couldn't be made.
+    #[error(transparent)]
+    Edit(#[from] EditError),
+    /// The program is valid KCL, but goes over the limits it was checked against.
+    #[error(transparent)]
+    Validation(#[from] LimitError),
+    /// The program parsed, but has errors that mean it can't run. There's always at least one.
+    #[error("{}", .0.iter().map(|diagnostic| diagnostic.message.as_str()).collect::<Vec<_>>().join("\n"))]
+    Invalid(Vec<Diagnostic>),
+    /// An arena couldn't be rebuilt into a program.
+    #[error(transparent)]
+    Arena(#[from] ArenaError),
+}
+
+impl KclAstError {
+    /// Where the error is, if it's about part of a program.
+    pub fn source_range(&self) -> Option<SourceRange> {
+        match self {
+            KclAstError::Parse { source_range, .. } => Some(*source_range),
+            KclAstError::Edit(EditError::NotABinaryExpression(source_range)) => Some(*source_range),
+            KclAstError::Validation(error) => Some(error.source_range()),
+            KclAstError::Invalid(diagnostics) => diagnostics.first().map(|diagnostic| diagnostic.source_range),
+            KclAstError::Serialization(_) | KclAstError::Refactor(_) | KclAstError::Edit(_) | KclAstError::Arena(_) => {
+                None
+            }
+        }
+    }
+}
+
+/// For errors from the parser.
+impl From<KclError> for KclAstError {
+    fn from(error: KclError) -> Self {
+        KclAstError::Parse {
+            source_range: error.source_ranges().first().copied().unwrap_or_default(),
+            message: error.message().to_owned(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for KclAstError {
+    fn from(error: serde_json::Error) -> Self {
+        KclAstError::Serialization(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{
+        diagnostics,
+        limits::{self, AstLimits},
+        transform,
+        types::{BinaryOperator, Program},
+    };
+
+    fn parse(code: &str) -> Result<Program, KclAstError> {
+        Ok(crate::parser::Parser::new(crate::token::lexer(code)).ast()?)
+    }
+
+    #[test]
+    fn test_error_categories() {
+        let err = parse("const x = )").unwrap_err();
+        assert!(matches!(err, KclAstError::Parse { .. }));
+        assert_eq!(err.source_range().unwrap().start(), 10);
+
+        let err: KclAstError = serde_json::from_str::<Program>("{").unwrap_err().into();
+        assert!(matches!(err, KclAstError::Serialization(_)));
+
+        let mut program = parse("const x = 1\nshow(x, x)").unwrap();
+        let err = transform::inline_single_use_const(&mut program, 0).unwrap_err();
+        assert!(matches!(err, KclAstError::Refactor(RefactorError::NotUsedOnce { .. })));
+
+        let err = transform::set_binary_operator(&mut program, SourceRange([0, 1]), BinaryOperator::Add).unwrap_err();
+        assert_eq!(
+            err,
+            KclAstError::Edit(EditError::NotABinaryExpression(SourceRange([0, 1])))
+        );
+
+        let limits = AstLimits {
+            max_nodes: 2,
+            ..Default::default()
+        };
+        let err = limits::enforce_limits(&program, &limits).unwrap_err();
+        assert!(matches!(err, KclAstError::Validation(LimitError::TooManyNodes { .. })));
+
+        let err = diagnostics::validate(&parse("const x = min(%, 1)").unwrap()).unwrap_err();
+        assert!(matches!(err, KclAstError::Invalid(_)));
+        assert_eq!(err.source_range(), Some(SourceRange([14, 15])));
+    }
+}
@@ -0,0 +1,237 @@
+//! Interned identifier names.
+//! A large program names the same few things (`line`, `sketch`, `startSketchAt`)
+//! over and over. With the `intern` feature, [`Identifier::name`](crate::ast::types::Identifier)
+//! is a [`Symbol`], so each distinct name is stored once, in a table shared by the whole
+//! process, however many identifiers use it.
+
+use std::{
+    borrow::Borrow,
+    collections::HashSet,
+    convert::Infallible,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+lazy_static::lazy_static! {
+    static ref INTERNER: Mutex<Interner> = Mutex::new(Interner::default());
+}
+
+/// An interned name. Two symbols are equal exactly when their names are.
+/// Serializes as the name itself, so it reads the same as a plain string to anything else.
+/// It derefs to `str` and compares with strings, so code written against a `String` name
+/// mostly works with either.
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    /// The name this symbol stands for.
+    pub fn to_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The same as [`Symbol::to_str`], under the name `String` gives it.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+// The table never holds two copies of a name while a symbol for it is alive, so comparing
+// pointers is the same as comparing names.
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        &*self.0 == other.as_str()
+    }
+}
+
+// Hashed like a `str`, so it can be looked up by one.
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl Default for Symbol {
+    fn default() -> Self {
+        Symbol::from("")
+    }
+}
+
+impl FromStr for Symbol {
+    type Err = Infallible;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(Symbol::from(name))
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        INTERNER.lock().unwrap_or_else(|e| e.into_inner()).intern(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol::from(name.as_str())
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(symbol: Symbol) -> Self {
+        symbol.to_str().to_owned()
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.to_str(), f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.to_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Symbol::from(name))
+    }
+}
+
+/// The table of names in use. A name stays in it while any symbol refers to it. Names nothing
+/// refers to any more are swept out whenever the table has doubled since the last sweep, so
+/// it stays in proportion to the names that are live, however many have come and gone.
+#[derive(Debug, Default)]
+struct Interner {
+    names: HashSet<Arc<str>>,
+    /// How many names were left after the last sweep.
+    swept_len: usize,
+}
+
+impl Interner {
+    /// Don't bother sweeping a table smaller than this.
+    const MIN_SWEEP_LEN: usize = 64;
+
+    fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(existing) = self.names.get(name) {
+            return Symbol(existing.clone());
+        }
+        if self.names.len() >= Self::MIN_SWEEP_LEN.max(2 * self.swept_len) {
+            // The table's own reference is the only one left to these.
+            self.names.retain(|name| Arc::strong_count(name) > 1);
+            self.swept_len = self.names.len();
+        }
+        let name: Arc<str> = Arc::from(name);
+        self.names.insert(name.clone());
+        Symbol(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_name_shares_one_entry() {
+        let program = crate::parser::Parser::new(crate::token::lexer("const sketch = 1\nshow(sketch)"))
+            .ast()
+            .unwrap();
+        let mut identifiers = Vec::new();
+        crate::ast::walk::walk(&program, |node| {
+            if let crate::ast::walk::NodeRef::Identifier(identifier) = node {
+                if identifier.name == "sketch" {
+                    identifiers.push(identifier.name.clone());
+                }
+            }
+        });
+
+        assert_eq!(identifiers.len(), 2);
+        assert_eq!(identifiers[0], identifiers[1]);
+        assert!(std::ptr::eq(identifiers[0].to_str(), identifiers[1].to_str()));
+        assert_eq!(identifiers[0].to_str(), "sketch");
+        assert_ne!(identifiers[0], Symbol::from("show"));
+    }
+
+    #[test]
+    fn test_symbol_serializes_as_its_name() {
+        let symbol: Symbol = "line".parse().unwrap();
+        let json = serde_json::to_string(&symbol).unwrap();
+        assert_eq!(json, r#""line""#);
+        assert_eq!(serde_json::from_str::<Symbol>(&json).unwrap(), symbol);
+    }
+
+    #[test]
+    fn test_unused_names_are_swept() {
+        // A table of its own, so other tests interning names can't get in the way.
+        let mut interner = Interner::default();
+        let kept = interner.intern("kept");
+        drop(interner.intern("dropped"));
+        for i in 0..Interner::MIN_SWEEP_LEN {
+            interner.intern(&format!("name{i}"));
+        }
+
+        assert!(interner.names.len() <= Interner::MIN_SWEEP_LEN);
+        assert!(!interner.names.contains("dropped"));
+        assert_eq!(interner.intern("kept"), kept);
+    }
+}
@@ -0,0 +1,243 @@
+//! Limits on how big and complex a program may be, for hosts that want to refuse a program
+//! before spending time executing it.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    ast::{
+        error::KclAstError,
+        types::Program,
+        walk::{self, NodeRef},
+    },
+    executor::SourceRange,
+};
+
+/// The most a program may contain. The default allows anything.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, ts_rs::TS)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub struct AstLimits {
+    /// How many nodes the whole AST may have, the program itself included.
+    pub max_nodes: usize,
+    /// How deep a node may be, counting the program itself as depth 0.
+    pub max_depth: usize,
+    /// How many stages a pipe may have, the value it starts from included.
+    pub max_pipe_stages: usize,
+    /// How many function expressions may be nested inside each other.
+    pub max_function_nesting: usize,
+}
+
+impl Default for AstLimits {
+    fn default() -> Self {
+        Self {
+            max_nodes: usize::MAX,
+            max_depth: usize::MAX,
+            max_pipe_stages: usize::MAX,
+            max_function_nesting: usize::MAX,
+        }
+    }
+}
+
+/// The limit a program broke, and where.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum LimitError {
+    #[error("the program has more than {limit} nodes")]
+    TooManyNodes { limit: usize, source_range: SourceRange },
+    #[error("the program nests expressions more than {limit} deep")]
+    TooDeep { limit: usize, source_range: SourceRange },
+    #[error("a pipe has {stages} stages, more than the limit of {limit}")]
+    TooManyPipeStages {
+        limit: usize,
+        stages: usize,
+        source_range: SourceRange,
+    },
+    #[error("functions are nested more than {limit} deep")]
+    FunctionsTooNested { limit: usize, source_range: SourceRange },
+}
+
+impl LimitError {
+    /// Where the limit was broken: the first node past the node limit or the depth limit, the
+    /// pipe with too many stages, or the function nested too deep.
+    pub fn source_range(&self) -> SourceRange {
+        match self {
+            LimitError::TooManyNodes { source_range, .. }
+            | LimitError::TooDeep { source_range, .. }
+            | LimitError::TooManyPipeStages { source_range, .. }
+            | LimitError::FunctionsTooNested { source_range, .. } => *source_range,
+        }
+    }
+}
+
+/// Check a program against `limits`. The limits are checked in the order [`AstLimits`] lists
+/// them, and the first one broken is returned, at the first place in the source it was broken.
+pub fn enforce_limits(program: &Program, limits: &AstLimits) -> Result<(), KclAstError> {
+    let mut nodes = 0;
+    let mut past_node_limit = None;
+    walk::walk(program, |node| {
+        nodes += 1;
+        if nodes > limits.max_nodes && past_node_limit.is_none() {
+            past_node_limit = Some(node);
+        }
+    });
+    if let Some(node) = past_node_limit {
+        return Err(LimitError::TooManyNodes {
+            limit: limits.max_nodes,
+            source_range: source_range(node),
+        }
+        .into());
+    }
+
+    if let Some(node) = first_deeper_than(NodeRef::Program(program), 0, limits.max_depth) {
+        return Err(LimitError::TooDeep {
+            limit: limits.max_depth,
+            source_range: source_range(node),
+        }
+        .into());
+    }
+
+    let mut long_pipe = None;
+    walk::walk(program, |node| {
+        if let NodeRef::PipeExpression(pipe) = node {
+            if pipe.body.len() > limits.max_pipe_stages && long_pipe.is_none() {
+                long_pipe = Some(pipe);
+            }
+        }
+    });
+    if let Some(pipe) = long_pipe {
+        return Err(LimitError::TooManyPipeStages {
+            limit: limits.max_pipe_stages,
+            stages: pipe.body.len(),
+            source_range: pipe.into(),
+        }
+        .into());
+    }
+
+    if let Some(node) = first_function_nested_past(NodeRef::Program(program), 0, limits.max_function_nesting) {
+        return Err(LimitError::FunctionsTooNested {
+            limit: limits.max_function_nesting,
+            source_range: source_range(node),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+fn source_range(node: NodeRef<'_>) -> SourceRange {
+    SourceRange([node.start(), node.end()])
+}
+
+/// The first node, depth-first, that is more than `max_depth` deep.
+fn first_deeper_than(node: NodeRef<'_>, depth: usize, max_depth: usize) -> Option<NodeRef<'_>> {
+    if depth > max_depth {
+        return Some(node);
+    }
+    node.children()
+        .into_iter()
+        .find_map(|child| first_deeper_than(child, depth + 1, max_depth))
+}
+
+/// The first function expression, depth-first, inside more than `max_nesting` others
+/// (itself included).
+fn first_function_nested_past(node: NodeRef<'_>, nesting: usize, max_nesting: usize) -> Option<NodeRef<'_>> {
+    let nesting = match node {
+        NodeRef::FunctionExpression(_) => nesting + 1,
+        _ => nesting,
+    };
+    if nesting > max_nesting {
+        return Some(node);
+    }
+    node.children()
+        .into_iter()
+        .find_map(|child| first_function_nested_past(child, nesting, max_nesting))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CODE: &str = r#"fn f = (a) => {
+  fn g = (b) => {
+    return b
+  }
+  return g(a)
+}
+const x = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> close(%)"#;
+
+    fn parse(code: &str) -> Program {
+        let tokens = crate::token::lexer(code);
+        crate::parser::Parser::new(tokens).ast().unwrap()
+    }
+
+    fn broken_limit(limits: &AstLimits) -> LimitError {
+        match enforce_limits(&parse(CODE), limits) {
+            Err(KclAstError::Validation(err)) => err,
+            other => panic!("expected a broken limit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_within_limits() {
+        assert_eq!(enforce_limits(&parse(CODE), &AstLimits::default()), Ok(()));
+    }
+
+    #[test]
+    fn test_max_nodes() {
+        let limits = AstLimits {
+            max_nodes: 5,
+            ..Default::default()
+        };
+        let err = broken_limit(&limits);
+        assert!(matches!(err, LimitError::TooManyNodes { limit: 5, .. }));
+        // The program, `fn f = ...`, its declarator, `f` and the function come first, so the
+        // parameter `a` is the sixth node.
+        let a = CODE.find("(a)").unwrap() + 1;
+        assert_eq!(err.source_range(), SourceRange([a, a + 1]));
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let limits = AstLimits {
+            max_depth: 6,
+            ..Default::default()
+        };
+        let err = broken_limit(&limits);
+        assert!(matches!(err, LimitError::TooDeep { limit: 6, .. }));
+        // The name in `fn g = ...` is inside the program, `fn f = ...`, its declarator, the
+        // function, its body, `fn g = ...` and its declarator, so it's 7 deep.
+        let g = CODE.find("fn g").unwrap() + "fn ".len();
+        assert_eq!(err.source_range(), SourceRange([g, g + 1]));
+    }
+
+    #[test]
+    fn test_max_pipe_stages() {
+        let limits = AstLimits {
+            max_pipe_stages: 2,
+            ..Default::default()
+        };
+        let err = broken_limit(&limits);
+        assert!(matches!(
+            err,
+            LimitError::TooManyPipeStages {
+                limit: 2,
+                stages: 3,
+                ..
+            }
+        ));
+        assert_eq!(err.source_range().start(), CODE.find("startSketchOn").unwrap());
+    }
+
+    #[test]
+    fn test_max_function_nesting() {
+        let limits = AstLimits {
+            max_function_nesting: 1,
+            ..Default::default()
+        };
+        let err = broken_limit(&limits);
+        assert!(matches!(err, LimitError::FunctionsTooNested { limit: 1, .. }));
+        assert_eq!(err.source_range().start(), CODE.find("(b)").unwrap());
+    }
+}
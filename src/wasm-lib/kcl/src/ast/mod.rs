@@ -1,2 +1,11 @@
+pub mod analysis;
+pub mod arena;
+pub mod diagnostics;
+pub mod error;
+#[cfg(feature = "intern")]
+pub mod intern;
+pub mod limits;
 pub mod modify;
+pub mod transform;
 pub mod types;
+pub mod walk;
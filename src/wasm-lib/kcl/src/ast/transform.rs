@@ -0,0 +1,1239 @@
+//! Transformations that rewrite the AST in place.
+
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::{
+    ast::{
+        analysis,
+        arena::ValueId,
+        error::KclAstError,
+        types::{
+            BinaryExpression, BinaryOperator, BinaryPart, BodyItem, CallExpression, Function, FunctionExpression,
+            Literal, LiteralIdentifier, NonCodeValue, ObjectExpression, PipeExpression, PipeSubstitution, Program,
+            ReturnStatement, UnaryExpression, UnaryOperator, Value, VariableKind,
+        },
+        walk::{
+            self, walk_binary_expression_mut, walk_function_expression_mut, walk_object_expression_mut, walk_part_mut,
+            walk_value_mut, NodeRef, VisitMut,
+        },
+    },
+    executor::SourceRange,
+};
+
+/// Why a refactoring couldn't be applied. The program is left unchanged when one is returned.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RefactorError {
+    #[error("statement {0} is not a `const` declaration of a single name")]
+    NotASingleConst(usize),
+    #[error("`{name}` is used {uses} times, but it can only be inlined if it's used once")]
+    NotUsedOnce { name: String, uses: usize },
+    #[error("the value of `{0}` calls a function, so inlining it could change when that call happens")]
+    ImpureInitializer(String),
+    #[error("`{0}` is used somewhere its value can't be written in place")]
+    CannotInline(String),
+    #[error("stage {0} of the pipe is not a function call")]
+    StageNotACall(usize),
+    #[error("stage {0} of the pipe doesn't use `%` exactly once")]
+    NotOneSubstitution(usize),
+}
+
+/// Why an edit at a source range couldn't be made. The program is left unchanged when one is
+/// returned.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    #[error("there is no binary expression at {}..{}", .0.start(), .0.end())]
+    NotABinaryExpression(SourceRange),
+    #[error("expression {0:?} is not a literal")]
+    NotALiteral(ValueId),
+    #[error("statement {0} is not a variable declaration")]
+    NotADeclaration(usize),
+    #[error("declaration {decl_index} has no declarator {declarator_index}")]
+    NoSuchDeclarator { decl_index: usize, declarator_index: usize },
+    #[error("there is no object at {}..{}", .0.start(), .0.end())]
+    NotAnObject(SourceRange),
+    #[error("the object has {len} properties, so there is no property {index}")]
+    NoSuchProperty { index: usize, len: usize },
+}
+
+/// Replace arithmetic on numeric literals with its result.
+/// Folding uses the same `f64` arithmetic as the executor. If a result would not be finite
+/// (overflow to infinity, or NaN from something like `0 / 0`) the expression is left as it is,
+/// so the executor still reports it the way it always has.
+pub fn fold_constants(program: &mut Program) {
+    Folder.visit_program(program);
+}
+
+struct Folder;
+
+impl VisitMut for Folder {
+    fn visit_value(&mut self, value: &mut Value) {
+        walk_value_mut(self, value);
+        let literal = match value {
+            Value::BinaryExpression(binary) => fold_binary(binary),
+            Value::UnaryExpression(unary) => fold_unary(unary),
+            _ => None,
+        };
+        if let Some(literal) = literal {
+            *value = Value::Literal(Box::new(literal));
+        }
+    }
+
+    fn visit_part(&mut self, part: &mut BinaryPart) {
+        walk_part_mut(self, part);
+        let literal = match part {
+            BinaryPart::BinaryExpression(binary) => fold_binary(binary),
+            BinaryPart::UnaryExpression(unary) => fold_unary(unary),
+            _ => None,
+        };
+        if let Some(literal) = literal {
+            *part = BinaryPart::Literal(Box::new(literal));
+        }
+    }
+}
+
+/// Fold a binary expression whose operands have already been folded, if both of them ended up
+/// as numbers.
+fn fold_binary(binary: &BinaryExpression) -> Option<Literal> {
+    let left = as_number(&binary.left)?;
+    let right = as_number(&binary.right)?;
+    let result = match binary.operator {
+        BinaryOperator::Add => left + right,
+        BinaryOperator::Sub => left - right,
+        BinaryOperator::Mul => left * right,
+        BinaryOperator::Div => left / right,
+        BinaryOperator::Mod => left % right,
+    };
+    number_literal(result, binary.start, binary.end)
+}
+
+fn fold_unary(unary: &UnaryExpression) -> Option<Literal> {
+    match unary.operator {
+        UnaryOperator::Neg => number_literal(-as_number(&unary.argument)?, unary.start, unary.end),
+        UnaryOperator::Not => None,
+    }
+}
+
+fn as_number(part: &BinaryPart) -> Option<f64> {
+    match part {
+        BinaryPart::Literal(literal) => match &literal.value {
+            serde_json::Value::Number(n) => n.as_f64(),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Build a literal for a folded number, or `None` if the number can't be represented.
+fn number_literal(value: f64, start: usize, end: usize) -> Option<Literal> {
+    if !value.is_finite() {
+        return None;
+    }
+
+    // Whole numbers are written the way the parser would have read them.
+    let value: serde_json::Value = if value.fract() == 0.0 && value.abs() < (1u64 << 53) as f64 {
+        (value as i64).into()
+    } else {
+        serde_json::Number::from_f64(value)?.into()
+    };
+    Some(Literal {
+        start,
+        end,
+        raw: value.to_string(),
+        value,
+    })
+}
+
+/// Merge runs of adjacent `const` declarations into one declaration with several declarators,
+/// keeping the declarators in order. Comments between the merged declarations are kept as
+/// inline comments on the merged declaration; blank lines between them are dropped.
+/// Function bodies are merged the same way.
+/// The merge is only in the AST: KCL has no syntax for declaring several names at once, so
+/// recasting writes each declarator as a declaration of its own, which parse back separately.
+pub fn merge_consecutive_consts(program: &mut Program) {
+    let old_body = std::mem::take(&mut program.body);
+    let mut old_non_code = std::mem::take(&mut program.non_code_meta.non_code_nodes);
+
+    let mut body: Vec<BodyItem> = Vec::with_capacity(old_body.len());
+    for (index, mut item) in old_body.into_iter().enumerate() {
+        if let BodyItem::VariableDeclaration(declaration) = &mut item {
+            for declarator in &mut declaration.declarations {
+                if let Value::FunctionExpression(function) = &mut declarator.init {
+                    merge_consecutive_consts(&mut function.body);
+                }
+            }
+        }
+
+        let merge = matches!(
+            (body.last(), &item),
+            (Some(BodyItem::VariableDeclaration(previous)), BodyItem::VariableDeclaration(next))
+                if previous.kind == VariableKind::Const && next.kind == VariableKind::Const
+        );
+        match (body.last_mut(), item) {
+            (Some(BodyItem::VariableDeclaration(previous)), BodyItem::VariableDeclaration(next)) if merge => {
+                previous.end = next.end;
+                previous.declarations.extend(next.declarations);
+
+                // Whatever followed the previous declaration is now inside the merged one.
+                let merged_index = body.len() - 1;
+                if let Some(between) = program.non_code_meta.non_code_nodes.get_mut(&merged_index) {
+                    between.retain(|node| node.value != NonCodeValue::NewLine);
+                    for node in between.iter_mut() {
+                        node.value = match std::mem::replace(&mut node.value, NonCodeValue::NewLine) {
+                            NonCodeValue::BlockComment { value, style }
+                            | NonCodeValue::NewLineBlockComment { value, style }
+                            | NonCodeValue::InlineComment { value, style } => {
+                                NonCodeValue::InlineComment { value, style }
+                            }
+                            NonCodeValue::NewLine => NonCodeValue::NewLine,
+                        };
+                    }
+                }
+            }
+            (_, item) => body.push(item),
+        }
+
+        if let Some(non_code) = old_non_code.remove(&index) {
+            program
+                .non_code_meta
+                .non_code_nodes
+                .entry(body.len() - 1)
+                .or_default()
+                .extend(non_code);
+        }
+    }
+    program
+        .non_code_meta
+        .non_code_nodes
+        .retain(|_, non_code| !non_code.is_empty());
+    program.body = body;
+}
+
+/// Replace every expression equal to `pattern` (ignoring source positions) with a copy of
+/// `replacement`, returning how many were replaced.
+/// A replacement isn't searched again, so it may contain the pattern. Operands of operators
+/// are only replaced when the replacement can be an operand, the property in a member
+/// expression's brackets only when the replacement is a literal or a name, and other names
+/// (callees, object keys, the object of a member expression, a property after a `.`) are never
+/// replaced.
+pub fn replace_matching(program: &mut Program, pattern: &Value, replacement: &Value) -> usize {
+    let mut replacer = Replacer {
+        pattern: pattern.into(),
+        replacement,
+        replacement_part: BinaryPart::try_from(replacement.clone()).ok(),
+        replacement_property: member_property(replacement),
+        count: 0,
+    };
+    replacer.visit_program(program);
+    replacer.count
+}
+
+struct Replacer<'a> {
+    pattern: NodeRef<'a>,
+    replacement: &'a Value,
+    replacement_part: Option<BinaryPart>,
+    replacement_property: Option<LiteralIdentifier>,
+    count: usize,
+}
+
+impl VisitMut for Replacer<'_> {
+    fn visit_value(&mut self, value: &mut Value) {
+        if NodeRef::from(&*value).ast_eq(self.pattern) {
+            *value = self.replacement.clone();
+            self.count += 1;
+            return;
+        }
+        walk_value_mut(self, value);
+    }
+
+    fn visit_part(&mut self, part: &mut BinaryPart) {
+        if let Some(replacement) = &self.replacement_part {
+            if NodeRef::from(&*part).ast_eq(self.pattern) {
+                *part = replacement.clone();
+                self.count += 1;
+                return;
+            }
+        }
+        walk_part_mut(self, part);
+    }
+
+    fn visit_member_property(&mut self, property: &mut LiteralIdentifier) {
+        if let Some(replacement) = &self.replacement_property {
+            if NodeRef::from(&*property).ast_eq(self.pattern) {
+                *property = replacement.clone();
+                self.count += 1;
+            }
+        }
+    }
+}
+
+/// The value as the property in a member expression's brackets, if it can be one.
+fn member_property(value: &Value) -> Option<LiteralIdentifier> {
+    match value {
+        Value::Literal(literal) => Some(LiteralIdentifier::Literal(literal.clone())),
+        Value::Identifier(identifier) => Some(LiteralIdentifier::Identifier(identifier.clone())),
+        _ => None,
+    }
+}
+
+/// Replace the only use of the `const` declared by the statement at `decl_index` with its
+/// value, and remove the declaration.
+/// The value must not call anything, and its one use must be somewhere an expression can go
+/// (not a callee or the object of a member expression, and in a member expression's brackets
+/// only if the value is a literal or a name) without any of the names it reads being rebound by
+/// a function in between.
+pub fn inline_single_use_const(program: &mut Program, decl_index: usize) -> Result<(), KclAstError> {
+    let declarator = match program.body.get(decl_index) {
+        Some(BodyItem::VariableDeclaration(declaration))
+            if declaration.kind == VariableKind::Const && declaration.declarations.len() == 1 =>
+        {
+            &declaration.declarations[0]
+        }
+        _ => return Err(RefactorError::NotASingleConst(decl_index).into()),
+    };
+    let name = declarator.id.name.to_string();
+    if analysis::calls_anything(&declarator.init) {
+        return Err(RefactorError::ImpureInitializer(name).into());
+    }
+    let uses: usize = program.body[decl_index + 1..]
+        .iter()
+        .map(|item| count_references(item.into(), &name))
+        .sum();
+    if uses != 1 {
+        return Err(RefactorError::NotUsedOnce { name, uses }.into());
+    }
+
+    let init = declarator.init.clone();
+    let init_names: HashSet<String> = analysis::free_names((&init).into())
+        .into_iter()
+        .map(str::to_owned)
+        .collect();
+    let mut inliner = Inliner {
+        name: &name,
+        init_part: BinaryPart::try_from(init.clone()).ok(),
+        init_property: member_property(&init),
+        init,
+        init_names,
+        bound: Vec::new(),
+        inlined: false,
+    };
+    for item in &mut program.body[decl_index + 1..] {
+        inliner.visit_body_item(item);
+    }
+    if !inliner.inlined {
+        return Err(RefactorError::CannotInline(name).into());
+    }
+
+    remove_body_item(program, decl_index);
+    Ok(())
+}
+
+/// Change the operator of the binary expression spanning exactly `source_range`, keeping its
+/// operands. The operands aren't regrouped, so recasting adds parens if the new operator binds
+/// differently.
+pub fn set_binary_operator(
+    program: &mut Program,
+    source_range: SourceRange,
+    operator: BinaryOperator,
+) -> Result<(), KclAstError> {
+    let mut setter = OperatorSetter {
+        source_range,
+        operator,
+        found: false,
+    };
+    setter.visit_program(program);
+    if !setter.found {
+        return Err(EditError::NotABinaryExpression(source_range).into());
+    }
+    Ok(())
+}
+
+struct OperatorSetter {
+    source_range: SourceRange,
+    operator: BinaryOperator,
+    found: bool,
+}
+
+impl VisitMut for OperatorSetter {
+    fn visit_binary_expression(&mut self, binary: &mut BinaryExpression) {
+        if SourceRange([binary.start, binary.end]) == self.source_range {
+            binary.operator = self.operator.clone();
+            self.found = true;
+            return;
+        }
+        walk_binary_expression_mut(self, binary);
+    }
+}
+
+/// Replace the value a declarator is initialized to, leaving its name alone. `decl_index` is
+/// the index of the declaration among the program's statements, and `declarator_index` the
+/// index of the declarator within it.
+pub fn set_declaration_init(
+    program: &mut Program,
+    decl_index: usize,
+    declarator_index: usize,
+    new: Value,
+) -> Result<(), KclAstError> {
+    let Some(BodyItem::VariableDeclaration(declaration)) = program.body.get_mut(decl_index) else {
+        return Err(EditError::NotADeclaration(decl_index).into());
+    };
+    let declarator = declaration
+        .declarations
+        .get_mut(declarator_index)
+        .ok_or(EditError::NoSuchDeclarator {
+            decl_index,
+            declarator_index,
+        })?;
+    declarator.init = new;
+    Ok(())
+}
+
+/// Move the property at index `from` of the object spanning exactly `object_range` to index `to`,
+/// shifting the properties in between along by one. Line breaks stay where they were in the
+/// list, so the object keeps its layout. Comments can't be written inside objects, so there are
+/// none to move.
+pub fn move_object_property(
+    program: &mut Program,
+    object_range: SourceRange,
+    from: usize,
+    to: usize,
+) -> Result<(), KclAstError> {
+    let mut mover = PropertyMover {
+        object_range,
+        from,
+        to,
+        result: None,
+    };
+    mover.visit_program(program);
+    match mover.result {
+        Some(result) => Ok(result?),
+        None => Err(EditError::NotAnObject(object_range).into()),
+    }
+}
+
+struct PropertyMover {
+    object_range: SourceRange,
+    from: usize,
+    to: usize,
+    /// Whether the property could be moved, once the object has been found.
+    result: Option<Result<(), EditError>>,
+}
+
+impl VisitMut for PropertyMover {
+    fn visit_object_expression(&mut self, object: &mut ObjectExpression) {
+        if SourceRange([object.start, object.end]) != self.object_range {
+            walk_object_expression_mut(self, object);
+            return;
+        }
+        let len = object.properties.len();
+        self.result = Some(match [self.from, self.to].into_iter().find(|index| *index >= len) {
+            Some(index) => Err(EditError::NoSuchProperty { index, len }),
+            None => {
+                let property = object.properties.remove(self.from);
+                object.properties.insert(self.to, property);
+                Ok(())
+            }
+        });
+    }
+}
+
+/// The nested calls a pipe is shorthand for: `a |> f(%) |> g(1, %)` is `g(1, f(a))`.
+/// Every stage after the first must be a call that uses `%` exactly once, since otherwise the
+/// nested form would drop a stage or run it twice. A pipe with one stage must be a call.
+pub fn pipe_to_calls(pipe: &PipeExpression) -> Result<CallExpression, KclAstError> {
+    let mut nested = pipe.body.first().cloned().ok_or(RefactorError::StageNotACall(0))?;
+    for (index, stage) in pipe.body.iter().enumerate().skip(1) {
+        let Value::CallExpression(call) = stage else {
+            return Err(RefactorError::StageNotACall(index).into());
+        };
+        let mut call = call.clone();
+        let substituted: usize = call.arguments.iter_mut().map(|arg| substitute(arg, &nested)).sum();
+        if substituted != 1 {
+            return Err(RefactorError::NotOneSubstitution(index).into());
+        }
+        nested = Value::CallExpression(call);
+    }
+
+    match nested {
+        Value::CallExpression(call) => Ok(*call),
+        _ => Err(RefactorError::StageNotACall(0).into()),
+    }
+}
+
+/// Rewrite every pipe with a single stage after its first value as the call it stands for:
+/// `x |> f(1, %)` becomes `f(1, x)`. Pipes with comments in them are left alone so the comments
+/// aren't lost, as are pipes [`pipe_to_calls`] can't rewrite.
+pub fn simplify_single_stage_pipes(program: &mut Program) {
+    walk::for_each_value_mut(program, |value| {
+        let Value::PipeExpression(pipe) = value else {
+            return;
+        };
+        if !is_redundant_pipe(pipe) {
+            return;
+        }
+        if let Ok(call) = pipe_to_calls(pipe) {
+            *value = Value::CallExpression(Box::new(call));
+        }
+    });
+}
+
+/// Whether a pipe is a value and one call that could be written as that call on its own.
+pub(crate) fn is_redundant_pipe(pipe: &PipeExpression) -> bool {
+    pipe.body.len() == 2
+        && pipe.non_code_meta.start.is_empty()
+        && pipe.non_code_meta.non_code_nodes.is_empty()
+        && pipe_to_calls(pipe).is_ok()
+}
+
+/// Mark every binary expression as written in parens, including the outermost one, the way the
+/// parser marks the parens a user wrote. Recasting with
+/// [`ParenPolicy::Preserve`](crate::ast::types::ParenPolicy::Preserve) then writes them all, so
+/// the grouping can be read without knowing the precedence of the operators. Unary operands
+/// aren't wrapped, since the parser can't read `(-a) * b`, and a unary expression always writes
+/// parens around a binary argument.
+pub fn fully_parenthesize(program: &mut Program) {
+    Parenthesizer.visit_program(program);
+}
+
+struct Parenthesizer;
+
+impl VisitMut for Parenthesizer {
+    fn visit_binary_expression(&mut self, binary: &mut BinaryExpression) {
+        binary.parenthesized = Some(true);
+        walk_binary_expression_mut(self, binary);
+    }
+}
+
+/// Replace the `%`s in a pipe stage's argument with `replacement`, returning how many there were.
+/// A `%` in a nested pipe or function belongs to that, so isn't replaced.
+fn substitute(value: &mut Value, replacement: &Value) -> usize {
+    match value {
+        Value::PipeSubstitution(_) => {
+            *value = replacement.clone();
+            1
+        }
+        Value::CallExpression(call) => call.arguments.iter_mut().map(|arg| substitute(arg, replacement)).sum(),
+        Value::ArrayExpression(array) => array
+            .elements
+            .iter_mut()
+            .map(|element| substitute(element, replacement))
+            .sum(),
+        Value::ObjectExpression(object) => object
+            .properties
+            .iter_mut()
+            .map(|property| substitute(&mut property.value, replacement))
+            .sum(),
+        Value::Literal(_)
+        | Value::Identifier(_)
+        | Value::BinaryExpression(_)
+        | Value::UnaryExpression(_)
+        | Value::FunctionExpression(_)
+        | Value::PipeExpression(_)
+        | Value::MemberExpression(_) => 0,
+    }
+}
+
+/// Remove every top-level declaration the program can't reach, returning how many names were
+/// removed. The statements that aren't declarations are what the program runs, so a name is
+/// reachable if one of them reads it, or reads something whose initializer reads it, and so
+/// on. Declarations that only read each other (or themselves, like a recursive function) are
+/// removed together. A declaration whose initializer calls a function is always kept, along
+/// with what it reads, since the call might have side effects.
+pub fn remove_unused(program: &mut Program) -> usize {
+    let graph = analysis::dependency_graph(program);
+    let mut to_visit: Vec<String> = Vec::new();
+    for item in &program.body {
+        match item {
+            BodyItem::VariableDeclaration(declaration) => to_visit.extend(
+                declaration
+                    .declarations
+                    .iter()
+                    .filter(|declarator| analysis::calls_anything(&declarator.init))
+                    .map(|declarator| declarator.id.name.to_string()),
+            ),
+            BodyItem::ExpressionStatement(_) | BodyItem::ReturnStatement(_) => {
+                to_visit.extend(analysis::free_names(item.into()).into_iter().map(str::to_owned))
+            }
+        }
+    }
+    let mut reachable: HashSet<String> = HashSet::new();
+    while let Some(name) = to_visit.pop() {
+        if let Some(dependencies) = graph.get(&name) {
+            to_visit.extend(dependencies.iter().filter(|name| !reachable.contains(*name)).cloned());
+        }
+        reachable.insert(name);
+    }
+
+    // Go backwards so removing a statement doesn't move the ones still to visit.
+    let mut removed = 0;
+    for index in (0..program.body.len()).rev() {
+        let BodyItem::VariableDeclaration(declaration) = &mut program.body[index] else {
+            continue;
+        };
+        let before = declaration.declarations.len();
+        declaration
+            .declarations
+            .retain(|declarator| reachable.contains(declarator.id.name.as_str()));
+        removed += before - declaration.declarations.len();
+        if declaration.declarations.is_empty() {
+            remove_body_item(program, index);
+        }
+    }
+    removed
+}
+
+/// In every pipe stage calling a function in `allowlist`, move a `%` passed as a later argument
+/// to be the first argument, returning how many calls were changed. Only stages that use `%`
+/// exactly once, as an argument on its own, are changed. Reordering arguments is only safe for
+/// functions that accept them either way round, which is what `allowlist` names.
+pub fn normalize_substitution_position(program: &mut Program, allowlist: &HashSet<String>) -> usize {
+    let mut moved = 0;
+    for_each_stage_call(program, &mut |call| {
+        if !allowlist.contains(call.callee.name.as_str()) {
+            return;
+        }
+        if let [index] = call.substitution_positions()[..] {
+            if index > 0 && matches!(call.arguments[index], Value::PipeSubstitution(_)) {
+                let substitution = call.arguments.remove(index);
+                call.arguments.insert(0, substitution);
+                moved += 1;
+            }
+        }
+    });
+    moved
+}
+
+/// Rewrite pipe stage calls whose only argument is `%` to match `explicit`, like the
+/// `explicit_substitution` format option does when recasting, returning how many calls were
+/// changed. When `explicit` is false, `|> f(%)` becomes `|> f()`, which the executor runs the
+/// same way. When it's true, a stage call with no arguments gets its `%` back, if the function
+/// takes any arguments: a stdlib function with parameters, or a function the program declares
+/// with parameters.
+pub fn set_explicit_substitution(program: &mut Program, explicit: bool) -> usize {
+    let mut takes_arguments = HashSet::new();
+    walk::walk(program, |node| {
+        if let NodeRef::VariableDeclarator(declarator) = node {
+            if let Value::FunctionExpression(function) = &declarator.init {
+                if !function.params.is_empty() {
+                    takes_arguments.insert(declarator.id.name.to_string());
+                }
+            }
+        }
+    });
+
+    let mut changed = 0;
+    for_each_stage_call(program, &mut |call| {
+        if explicit && call.arguments.is_empty() {
+            let takes_substitution = match &call.function {
+                Function::StdLib { func } => !func.args().is_empty(),
+                Function::InMemory => takes_arguments.contains(call.callee.name.as_str()),
+            };
+            if takes_substitution {
+                call.arguments.push(Value::PipeSubstitution(Box::new(PipeSubstitution {
+                    start: call.end,
+                    end: call.end,
+                })));
+                changed += 1;
+            }
+        } else if !explicit && matches!(call.arguments[..], [Value::PipeSubstitution(_)]) {
+            call.arguments.clear();
+            changed += 1;
+        }
+    });
+    changed
+}
+
+/// Call `f` on every call that is a stage of a pipe (other than the first), anywhere in the
+/// program.
+fn for_each_stage_call(program: &mut Program, f: &mut impl FnMut(&mut CallExpression)) {
+    walk::for_each_value_mut(program, |value| {
+        if let Value::PipeExpression(pipe) = value {
+            for stage in pipe.body.iter_mut().skip(1) {
+                if let Value::CallExpression(call) = stage {
+                    f(call);
+                }
+            }
+        }
+    });
+}
+
+/// Turn the expression a function body ends with into a `return` of it, in every function in
+/// the program. Bodies that already end in a `return`, or in a declaration, are left alone.
+pub fn make_returns_explicit(program: &mut Program) {
+    for_each_function(program, &mut |function| {
+        let Some(last) = function.body.body.last_mut() else {
+            return;
+        };
+        if let BodyItem::ExpressionStatement(statement) = last {
+            *last = BodyItem::ReturnStatement(ReturnStatement {
+                start: statement.start,
+                end: statement.end,
+                argument: statement.expression.clone(),
+            });
+        }
+    });
+}
+
+/// Call `f` on every function expression in the program, inner functions before the ones
+/// around them.
+fn for_each_function(program: &mut Program, f: &mut impl FnMut(&mut FunctionExpression)) {
+    walk::for_each_value_mut(program, |value| {
+        if let Value::FunctionExpression(function) = value {
+            f(function);
+        }
+    });
+}
+
+/// How many times a node reads `name`, not counting inside functions that bind their own `name`.
+fn count_references(node: NodeRef<'_>, name: &str) -> usize {
+    match node {
+        NodeRef::Identifier(identifier) => usize::from(identifier.name == name),
+        NodeRef::VariableDeclarator(declarator) => count_references((&declarator.init).into(), name),
+        NodeRef::ObjectProperty(property) => count_references((&property.value).into(), name),
+        NodeRef::MemberExpression(member) => {
+            let property = match (&member.property, member.computed) {
+                (LiteralIdentifier::Identifier(identifier), true) => usize::from(identifier.name == name),
+                _ => 0,
+            };
+            count_references((&member.object).into(), name) + property
+        }
+        NodeRef::FunctionExpression(function) if bound_names(function).any(|bound| bound == name) => 0,
+        _ => {
+            let mut count = 0;
+            node.for_each_child(|child| count += count_references(child, name));
+            count
+        }
+    }
+}
+
+/// The names a function binds: its parameters and its body's declarations.
+fn bound_names(function: &FunctionExpression) -> impl Iterator<Item = &str> {
+    let declared = function
+        .body
+        .body
+        .iter()
+        .filter_map(|item| match item {
+            BodyItem::VariableDeclaration(declaration) => Some(declaration),
+            _ => None,
+        })
+        .flat_map(|declaration| &declaration.declarations);
+    function
+        .params
+        .iter()
+        .map(|param| param.name.as_str())
+        .chain(declared.map(|declarator| declarator.id.name.as_str()))
+}
+
+struct Inliner<'a> {
+    name: &'a str,
+    init: Value,
+    init_part: Option<BinaryPart>,
+    init_property: Option<LiteralIdentifier>,
+    /// The names `init` reads, which mustn't be rebound where it's inlined.
+    init_names: HashSet<String>,
+    /// The names bound by the functions we're inside.
+    bound: Vec<String>,
+    inlined: bool,
+}
+
+impl Inliner<'_> {
+    fn can_inline(&self) -> bool {
+        !self.bound.iter().any(|name| self.init_names.contains(name))
+    }
+}
+
+impl VisitMut for Inliner<'_> {
+    fn visit_value(&mut self, value: &mut Value) {
+        match value {
+            Value::Identifier(identifier) if identifier.name == self.name => {
+                if self.can_inline() {
+                    *value = self.init.clone();
+                    self.inlined = true;
+                }
+            }
+            _ => walk_value_mut(self, value),
+        }
+    }
+
+    fn visit_part(&mut self, part: &mut BinaryPart) {
+        match part {
+            BinaryPart::Identifier(identifier) if identifier.name == self.name => {
+                if let (Some(replacement), true) = (&self.init_part, self.can_inline()) {
+                    *part = replacement.clone();
+                    self.inlined = true;
+                }
+            }
+            _ => walk_part_mut(self, part),
+        }
+    }
+
+    fn visit_member_property(&mut self, property: &mut LiteralIdentifier) {
+        if let LiteralIdentifier::Identifier(identifier) = property {
+            if identifier.name == self.name {
+                if let (Some(replacement), true) = (&self.init_property, self.can_inline()) {
+                    *property = replacement.clone();
+                    self.inlined = true;
+                }
+            }
+        }
+    }
+
+    fn visit_function_expression(&mut self, function: &mut FunctionExpression) {
+        let bound: Vec<String> = bound_names(function).map(str::to_owned).collect();
+        if bound.iter().any(|name| name == self.name) {
+            return;
+        }
+        let outer = self.bound.len();
+        self.bound.extend(bound);
+        walk_function_expression_mut(self, function);
+        self.bound.truncate(outer);
+    }
+}
+
+/// Remove a top-level statement, keeping every other statement's comments where they were.
+/// Comments after the removed statement move to the end of the statement before it, apart
+/// from an inline comment, which was about the removed statement.
+fn remove_body_item(program: &mut Program, index: usize) {
+    program.body.remove(index);
+
+    let mut old_non_code = std::mem::take(&mut program.non_code_meta.non_code_nodes);
+    let mut removed = old_non_code.remove(&index).unwrap_or_default();
+    removed.retain(|node| !matches!(node.value, NonCodeValue::InlineComment { .. }));
+    for (i, non_code) in old_non_code {
+        let i = if i > index { i - 1 } else { i };
+        program.non_code_meta.non_code_nodes.insert(i, non_code);
+    }
+    match index.checked_sub(1) {
+        Some(previous) => program
+            .non_code_meta
+            .non_code_nodes
+            .entry(previous)
+            .or_default()
+            .extend(removed),
+        None => program.non_code_meta.start.extend(removed),
+    }
+    program
+        .non_code_meta
+        .non_code_nodes
+        .retain(|_, non_code| !non_code.is_empty());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::types::{FormatOptions, ParenPolicy};
+
+    fn parse(code: &str) -> Program {
+        let tokens = crate::token::lexer(code);
+        crate::parser::Parser::new(tokens).ast().unwrap()
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        let mut program = parse("const x = 1 + 2 * 3\nconst y = [10 / 4, -(2 - 5), z * 2]");
+        fold_constants(&mut program);
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "const x = 7\nconst y = [2.5, 3, z * 2]\n"
+        );
+    }
+
+    #[test]
+    fn test_fold_constants_leaves_overflow_unfolded() {
+        // The lexer has no exponent syntax, so spell out 1e308.
+        let big = format!("1{}", "0".repeat(308));
+        let mut program = parse(&format!("const x = {} * 10\nconst y = 0 / 0\nconst z = 2 * 3", big));
+        let before = program.clone();
+        fold_constants(&mut program);
+
+        let BodyItem::VariableDeclaration(x) = &program.body[0] else {
+            panic!("expected a variable declaration");
+        };
+        assert!(matches!(x.declarations[0].init, Value::BinaryExpression(_)));
+        assert_eq!(program.body[0], before.body[0]);
+        assert_eq!(program.body[1], before.body[1]);
+
+        let BodyItem::VariableDeclaration(z) = &program.body[2] else {
+            panic!("expected a variable declaration");
+        };
+        let Value::Literal(literal) = &z.declarations[0].init else {
+            panic!("expected a literal");
+        };
+        assert_eq!(literal.value, serde_json::json!(6));
+    }
+
+    #[test]
+    fn test_merge_consecutive_consts() {
+        let code = r#"const a = 1
+// the second one
+const b = 2
+let c = 3
+show(a + b + c)
+"#;
+        let mut program = parse(code);
+        merge_consecutive_consts(&mut program);
+
+        assert_eq!(program.body.len(), 3);
+        let BodyItem::VariableDeclaration(merged) = &program.body[0] else {
+            panic!("expected a variable declaration");
+        };
+        let names: Vec<_> = merged.declarations.iter().map(|d| d.id.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        let recast = program.recast(&Default::default(), 0);
+        assert_eq!(
+            recast,
+            r#"const a = 1
+const b = 2 // the second one
+let c = 3
+show(a + b + c)
+"#
+        );
+        assert_eq!(parse(&recast).body.len(), 4);
+    }
+
+    fn parse_value(code: &str) -> Value {
+        let BodyItem::ExpressionStatement(statement) = parse(code).body.remove(0) else {
+            panic!("expected an expression statement");
+        };
+        statement.expression
+    }
+
+    #[test]
+    fn test_replace_matching() {
+        let mut program = parse("const x = a + 1\nconst y = (a + 1) * 2\nconst z = f(a + 1, a + 10, b + 1)");
+        let count = replace_matching(&mut program, &parse_value("a + 1"), &parse_value("a + 2"));
+        assert_eq!(count, 3);
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "const x = a + 2\nconst y = (a + 2) * 2\nconst z = f(a + 2, a + 10, b + 1)\n"
+        );
+    }
+
+    #[test]
+    fn test_replace_matching_replacement_contains_pattern() {
+        let mut program = parse("const x = a * 2\nconst y = [a, b]");
+        let count = replace_matching(&mut program, &parse_value("a"), &parse_value("a + 1"));
+        assert_eq!(count, 2);
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "const x = (a + 1) * 2\nconst y = [a + 1, b]\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_single_use_const() {
+        let mut program = parse("const width = 10\nconst offset = width + 1\nshow(offset * 2)");
+        inline_single_use_const(&mut program, 1).unwrap();
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "const width = 10\nshow((width + 1) * 2)\n"
+        );
+    }
+
+    #[test]
+    fn test_replace_matching_member_property() {
+        let mut program = parse("const x = xs[i] + ys[i][j]\nconst y = point.i");
+        let count = replace_matching(&mut program, &parse_value("i"), &parse_value("0"));
+        assert_eq!(count, 2);
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "const x = xs[0] + ys[0][j]\nconst y = point.i\n"
+        );
+
+        let count = replace_matching(&mut program, &parse_value("0"), &parse_value("k"));
+        assert_eq!(count, 2);
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "const x = xs[k] + ys[k][j]\nconst y = point.i\n"
+        );
+    }
+
+    #[test]
+    fn test_inline_single_use_const_member_property() {
+        let mut program = parse("const name = 'width'\nshow(sizes[name])");
+        inline_single_use_const(&mut program, 0).unwrap();
+        assert_eq!(program.recast(&Default::default(), 0), "show(sizes['width'])\n");
+    }
+
+    #[test]
+    fn test_inline_single_use_const_used_twice() {
+        let mut program = parse("const offset = 1\nshow(offset, offset * 2)");
+        let before = program.clone();
+        assert_eq!(
+            inline_single_use_const(&mut program, 0),
+            Err(RefactorError::NotUsedOnce {
+                name: "offset".to_owned(),
+                uses: 2
+            }
+            .into())
+        );
+        assert_eq!(program, before);
+    }
+
+    #[test]
+    fn test_remove_unused() {
+        let code = r#"const a = 1
+const b = a + 1
+// keep this
+const d = 5
+const c = b * 2
+const part001 = startSketchOn('XY')
+show(d)
+"#;
+        let mut program = parse(code);
+        assert_eq!(remove_unused(&mut program), 3);
+
+        let recast = program.recast(&Default::default(), 0);
+        assert!(recast.contains("// keep this"));
+        assert!(recast.ends_with("const d = 5\nconst part001 = startSketchOn('XY')\nshow(d)\n"));
+        assert_eq!(program.body.len(), 3);
+
+        assert_eq!(remove_unused(&mut program), 0);
+    }
+
+    #[test]
+    fn test_remove_unused_unreachable_cycles() {
+        let code = r#"fn ping = (n) => {
+  return pong(n)
+}
+fn pong = (n) => {
+  return ping(n)
+}
+fn countdown = (n) => {
+  return countdown(n - 1)
+}
+fn double = (n) => {
+  return n * 2
+}
+const size = double(2)
+show(size)
+"#;
+        let mut program = parse(code);
+        assert_eq!(remove_unused(&mut program), 3);
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "fn double = (n) => {\n  return n * 2\n}\nconst size = double(2)\nshow(size)\n"
+        );
+    }
+
+    #[test]
+    fn test_set_binary_operator() {
+        let mut program = parse("const x = a + b\nconst y = f(c, a + b)");
+        set_binary_operator(&mut program, SourceRange([10, 15]), BinaryOperator::Sub).unwrap();
+        set_binary_operator(&mut program, SourceRange([31, 36]), BinaryOperator::Mul).unwrap();
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "const x = a - b\nconst y = f(c, a * b)\n"
+        );
+
+        let before = program.clone();
+        assert_eq!(
+            set_binary_operator(&mut program, SourceRange([10, 11]), BinaryOperator::Add),
+            Err(EditError::NotABinaryExpression(SourceRange([10, 11])).into())
+        );
+        assert_eq!(program, before);
+    }
+
+    #[test]
+    fn test_set_declaration_init() {
+        let mut program = parse("const x = 5\nshow(x)");
+        let call = parse("min(1, 2)").body.remove(0);
+        let BodyItem::ExpressionStatement(call) = call else {
+            panic!("expected an expression statement");
+        };
+        set_declaration_init(&mut program, 0, 0, call.expression).unwrap();
+        assert_eq!(program.recast(&Default::default(), 0), "const x = min(1, 2)\nshow(x)\n");
+
+        let before = program.clone();
+        let new = || Value::Literal(Box::new(Literal::from_f64(1.0)));
+        assert_eq!(
+            set_declaration_init(&mut program, 0, 1, new()),
+            Err(EditError::NoSuchDeclarator {
+                decl_index: 0,
+                declarator_index: 1
+            }
+            .into())
+        );
+        assert_eq!(
+            set_declaration_init(&mut program, 1, 0, new()),
+            Err(EditError::NotADeclaration(1).into())
+        );
+        assert_eq!(
+            set_declaration_init(&mut program, 2, 0, new()),
+            Err(EditError::NotADeclaration(2).into())
+        );
+        assert_eq!(program, before);
+    }
+
+    #[test]
+    fn test_move_object_property() {
+        let code = "// sizes\nconst size = {\n  width: 1,\n  height: 2,\n  depth: 3\n}\nshow(size)";
+        let mut program = parse(code);
+        let object_range = SourceRange([code.find('{').unwrap(), code.find('}').unwrap() + 1]);
+        move_object_property(&mut program, object_range, 2, 0).unwrap();
+
+        // The comment before the declaration stays where it was.
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "// sizes\nconst size = { depth: 3, width: 1, height: 2 }\nshow(size)\n"
+        );
+
+        let before = program.clone();
+        assert_eq!(
+            move_object_property(&mut program, object_range, 0, 3),
+            Err(EditError::NoSuchProperty { index: 3, len: 3 }.into())
+        );
+        assert_eq!(
+            move_object_property(&mut program, SourceRange([0, 1]), 0, 1),
+            Err(EditError::NotAnObject(SourceRange([0, 1])).into())
+        );
+        assert_eq!(program, before);
+    }
+
+    #[test]
+    fn test_pipe_to_calls() {
+        let code = "const x = startSketchOn('XY')\n  |> startProfileAt([0, 0], %)\n  |> line([0, 10], %)";
+        let mut program = parse(code);
+        let BodyItem::VariableDeclaration(declaration) = &mut program.body[0] else {
+            panic!("expected a variable declaration");
+        };
+        let Value::PipeExpression(pipe) = &declaration.declarations[0].init else {
+            panic!("expected a pipe expression");
+        };
+        let call = pipe_to_calls(pipe).unwrap();
+        declaration.declarations[0].init = Value::CallExpression(Box::new(call));
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "const x = line([0, 10], startProfileAt([0, 0], startSketchOn('XY')))\n"
+        );
+    }
+
+    #[test]
+    fn test_pipe_to_calls_rejects_non_call_stage() {
+        let program = parse("const x = f(1)\n  |> g(%)\n  |> h");
+        let BodyItem::VariableDeclaration(declaration) = &program.body[0] else {
+            panic!("expected a variable declaration");
+        };
+        let Value::PipeExpression(pipe) = &declaration.declarations[0].init else {
+            panic!("expected a pipe expression");
+        };
+        assert_eq!(pipe_to_calls(pipe), Err(RefactorError::StageNotACall(2).into()));
+    }
+
+    #[test]
+    fn test_normalize_substitution_position() {
+        let code = "const x = startSketchOn('XY')\n  |> startProfileAt([0, 0], %)\n  |> line([0, 10], %)";
+        let mut program = parse(code);
+        let allowlist = HashSet::from(["line".to_owned()]);
+        assert_eq!(normalize_substitution_position(&mut program, &allowlist), 1);
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "const x = startSketchOn('XY')\n  |> startProfileAt([0, 0], %)\n  |> line(%, [0, 10])\n"
+        );
+
+        // Already first, so nothing to do.
+        assert_eq!(normalize_substitution_position(&mut program, &allowlist), 0);
+    }
+
+    #[test]
+    fn test_set_explicit_substitution() {
+        let code = r#"fn g = (x) => {
+  return x
+}
+fn h = () => {
+  return 1
+}
+const x = startSketchOn('XY')
+  |> startProfileAt([0, 0], %)
+  |> close(%)
+  |> g(%)
+  |> h()
+"#;
+        let mut program = parse(code);
+
+        // Only a sole `%` is elided.
+        assert_eq!(set_explicit_substitution(&mut program, false), 2);
+        assert!(program
+            .recast(&Default::default(), 0)
+            .ends_with("  |> startProfileAt([0, 0], %)\n  |> close()\n  |> g()\n  |> h()\n"));
+
+        // `h` takes no arguments, so it doesn't get a `%` it never had.
+        assert_eq!(set_explicit_substitution(&mut program, true), 2);
+        assert_eq!(program.recast(&Default::default(), 0), code);
+        assert_eq!(set_explicit_substitution(&mut program, true), 0);
+    }
+
+    #[test]
+    fn test_make_returns_explicit() {
+        let code = r#"fn clamp = (x) => {
+  const y = x * 2
+  min(y, 10)
+}
+fn double = (x) => {
+  return x * 2
+}
+"#;
+        let mut program = parse(code);
+        let mut untouched = program.clone();
+        make_returns_explicit(&mut program);
+
+        let recast = program.recast(&Default::default(), 0);
+        assert!(recast.contains("  return min(y, 10)\n"));
+        assert!(!recast.contains("  min(y, 10)\n"));
+        // `double` already returned its value.
+        program.body.remove(0);
+        untouched.body.remove(0);
+        assert_eq!(program, untouched);
+    }
+
+    #[test]
+    fn test_fully_parenthesize() {
+        let code = r#"const x = a + b * c
+const y = a * b + c * d - e
+const z = -(a + b * c)
+const w = -a * b
+"#;
+        let mut program = parse(code);
+        fully_parenthesize(&mut program);
+
+        let preserve = FormatOptions {
+            parens: ParenPolicy::Preserve,
+            ..Default::default()
+        };
+        let recast = program.recast(&preserve, 0);
+        assert_eq!(
+            recast,
+            r#"const x = (a + (b * c))
+const y = (((a * b) + (c * d)) - e)
+const z = -(a + (b * c))
+const w = (-a * b)
+"#
+        );
+        assert!(crate::ast::walk::ast_eq(&program, &parse(&recast)));
+
+        // Only Preserve reads the marks, so other policies recast as before.
+        assert_eq!(program.recast(&Default::default(), 0), code);
+    }
+
+    #[test]
+    fn test_simplify_single_stage_pipes() {
+        let code = r#"const x = 5 |> double(%)
+const part = startSketchAt([0, 0])
+  |> line([1, 1], %)
+  |> close(%)
+show(x |> f(1, %), part)
+"#;
+        let mut program = parse(code);
+        simplify_single_stage_pipes(&mut program);
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            r#"const x = double(5)
+const part = startSketchAt([0, 0])
+  |> line([1, 1], %)
+  |> close(%)
+show(f(1, x), part)
+"#
+        );
+    }
+}
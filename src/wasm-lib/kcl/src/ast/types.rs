@@ -10,6 +10,7 @@ use serde_json::Map;
 use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, DocumentSymbol, Range as LspRange, SymbolKind};
 
 use crate::{
+    ast::walk::NodeRef,
     errors::{KclError, KclErrorDetails},
     executor::{ExecutorContext, MemoryItem, Metadata, PipeInfo, ProgramMemory, SourceRange, UserVal},
     parser::PIPE_OPERATOR,
@@ -26,6 +27,50 @@ pub struct Program {
 }
 
 impl Program {
+    /// How many statements the program has. With `recursive`, statements in function bodies
+    /// are counted too.
+    pub fn statement_count(&self, recursive: bool) -> usize {
+        if !recursive {
+            return self.body.len();
+        }
+        let mut count = 0;
+        NodeRef::Program(self).walk(&mut |node| {
+            if let NodeRef::Program(body) = node {
+                count += body.body.len();
+            }
+        });
+        count
+    }
+
+    /// How many expressions the program has, including ones nested inside other expressions
+    /// and in function bodies. Names that aren't values on their own (declared names,
+    /// parameters, callees, object keys and the parts of a member expression) don't count.
+    pub fn expression_count(&self) -> usize {
+        fn count(node: NodeRef<'_>) -> usize {
+            match node {
+                NodeRef::VariableDeclarator(declarator) => count((&declarator.init).into()),
+                NodeRef::FunctionExpression(function) => 1 + count(NodeRef::Program(&function.body)),
+                NodeRef::CallExpression(call) => 1 + call.arguments.iter().map(|arg| count(arg.into())).sum::<usize>(),
+                NodeRef::ObjectProperty(property) => count((&property.value).into()),
+                NodeRef::MemberExpression(_) => 1,
+                _ => {
+                    let is_statement = matches!(
+                        node,
+                        NodeRef::Program(_)
+                            | NodeRef::ExpressionStatement(_)
+                            | NodeRef::VariableDeclaration(_)
+                            | NodeRef::ReturnStatement(_)
+                    );
+                    let mut total = usize::from(!is_statement);
+                    node.for_each_child(|child| total += count(child));
+                    total
+                }
+            }
+        }
+
+        count(NodeRef::Program(self))
+    }
+
     pub fn recast(&self, options: &FormatOptions, indentation_level: usize) -> String {
         let indentation = options.get_indentation(indentation_level);
         let result =
@@ -41,6 +86,16 @@ impl Program {
                         .declarations
                         .iter()
                         .fold(String::new(), |mut output, declaration| {
+                            // There's no syntax for declaring several names at once, so each
+                            // declarator is written as a declaration of its own.
+                            if !output.is_empty() {
+                                if options.semicolons == SemicolonPolicy::Always
+                                    && variable_declaration.kind != VariableKind::Fn
+                                {
+                                    output.push(';');
+                                }
+                                output.push('\n');
+                            }
                             let _ = write!(
                                 output,
                                 "{}{} {} = {}",
@@ -59,6 +114,18 @@ impl Program {
                         )
                     }
                 })
+                .zip(&self.body)
+                .map(|(recast_str, statement)| match (options.semicolons, statement) {
+                    // Function declarations end in a brace, they don't need a terminator.
+                    (SemicolonPolicy::Never, _)
+                    | (
+                        SemicolonPolicy::Always,
+                        BodyItem::VariableDeclaration(VariableDeclaration {
+                            kind: VariableKind::Fn, ..
+                        }),
+                    ) => recast_str,
+                    (SemicolonPolicy::Always, _) => recast_str + ";",
+                })
                 .enumerate()
                 .fold(String::new(), |mut output, (index, recast_str)| {
                     let start_string = if index == 0 {
@@ -66,11 +133,18 @@ impl Program {
                         if self.non_code_meta.start.is_empty() {
                             indentation.to_string()
                         } else {
-                            self.non_code_meta
+                            let mut start_string: String = self
+                                .non_code_meta
                                 .start
                                 .iter()
-                                .map(|start| start.format(&indentation))
-                                .collect()
+                                .map(|start| start.format(&indentation, options))
+                                .collect();
+                            // A block comment doesn't end its own line, the first statement
+                            // still has to go on the next one.
+                            if !start_string.ends_with('\n') {
+                                start_string.push('\n');
+                            }
+                            start_string
                         }
                     } else {
                         // Do nothing, we already applied the indentation elsewhere.
@@ -88,14 +162,20 @@ impl Program {
                     let custom_white_space_or_comment = match self.non_code_meta.non_code_nodes.get(&index) {
                         Some(noncodes) => noncodes
                             .iter()
-                            .map(|custom_white_space_or_comment| custom_white_space_or_comment.format(&indentation))
+                            .map(|custom_white_space_or_comment| {
+                                custom_white_space_or_comment.format(&indentation, options)
+                            })
                             .collect::<String>(),
                         None => String::new(),
                     };
                     let end_string = if custom_white_space_or_comment.is_empty() {
                         maybe_line_break
-                    } else {
+                    } else if custom_white_space_or_comment.ends_with('\n') {
                         custom_white_space_or_comment
+                    } else {
+                        // Likewise after a block comment, or the next statement would
+                        // continue the comment's line.
+                        custom_white_space_or_comment + &maybe_line_break
                     };
 
                     let _ = write!(output, "{}{}{}", start_string, recast_str, end_string);
@@ -407,7 +487,7 @@ impl Value {
             Value::ArrayExpression(array_exp) => array_exp.recast(options, indentation_level, is_in_pipe),
             Value::ObjectExpression(ref obj_exp) => obj_exp.recast(options, indentation_level, is_in_pipe),
             Value::MemberExpression(mem_exp) => mem_exp.recast(),
-            Value::Literal(literal) => literal.recast(),
+            Value::Literal(literal) => literal.recast(options),
             Value::FunctionExpression(func_exp) => func_exp.recast(options, indentation_level),
             Value::CallExpression(call_exp) => call_exp.recast(options, indentation_level, is_in_pipe),
             Value::Identifier(ident) => ident.name.to_string(),
@@ -558,6 +638,36 @@ pub enum BinaryPart {
     MemberExpression(Box<MemberExpression>),
 }
 
+impl From<BinaryPart> for Value {
+    fn from(part: BinaryPart) -> Self {
+        match part {
+            BinaryPart::Literal(literal) => Value::Literal(literal),
+            BinaryPart::Identifier(identifier) => Value::Identifier(identifier),
+            BinaryPart::BinaryExpression(binary_expression) => Value::BinaryExpression(binary_expression),
+            BinaryPart::CallExpression(call_expression) => Value::CallExpression(call_expression),
+            BinaryPart::UnaryExpression(unary_expression) => Value::UnaryExpression(unary_expression),
+            BinaryPart::MemberExpression(member_expression) => Value::MemberExpression(member_expression),
+        }
+    }
+}
+
+/// Values that can't be the operand of an operator are given back unchanged.
+impl TryFrom<Value> for BinaryPart {
+    type Error = Value;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Literal(literal) => Ok(BinaryPart::Literal(literal)),
+            Value::Identifier(identifier) => Ok(BinaryPart::Identifier(identifier)),
+            Value::BinaryExpression(binary_expression) => Ok(BinaryPart::BinaryExpression(binary_expression)),
+            Value::CallExpression(call_expression) => Ok(BinaryPart::CallExpression(call_expression)),
+            Value::UnaryExpression(unary_expression) => Ok(BinaryPart::UnaryExpression(unary_expression)),
+            Value::MemberExpression(member_expression) => Ok(BinaryPart::MemberExpression(member_expression)),
+            other => Err(other),
+        }
+    }
+}
+
 impl From<BinaryPart> for crate::executor::SourceRange {
     fn from(value: BinaryPart) -> Self {
         Self([value.start(), value.end()])
@@ -602,7 +712,7 @@ impl BinaryPart {
 
     fn recast(&self, options: &FormatOptions, indentation_level: usize) -> String {
         match &self {
-            BinaryPart::Literal(literal) => literal.recast(),
+            BinaryPart::Literal(literal) => literal.recast(options),
             BinaryPart::Identifier(identifier) => identifier.name.to_string(),
             BinaryPart::BinaryExpression(binary_expression) => binary_expression.recast(options),
             BinaryPart::CallExpression(call_expression) => call_expression.recast(options, indentation_level, false),
@@ -647,7 +757,7 @@ impl BinaryPart {
         new_pipe_info.is_in_pipe = false;
 
         match self {
-            BinaryPart::Literal(literal) => Ok(literal.into()),
+            BinaryPart::Literal(literal) => literal.get_result(),
             BinaryPart::Identifier(identifier) => {
                 let value = memory.get(&identifier.name, identifier.into())?;
                 Ok(value.clone())
@@ -721,12 +831,21 @@ impl NonCodeNode {
         }
     }
 
-    pub fn format(&self, indentation: &str) -> String {
+    pub fn format(&self, indentation: &str, options: &FormatOptions) -> String {
+        // Block comments are always written as they are.
+        fn line_text(value: &str, normalize: bool) -> &str {
+            if normalize {
+                value.trim()
+            } else {
+                value
+            }
+        }
+
         match &self.value {
             NonCodeValue::InlineComment {
                 value,
                 style: CommentStyle::Line,
-            } => format!(" // {}\n", value),
+            } => format!(" // {}\n", line_text(value, options.normalize_comments)),
             NonCodeValue::InlineComment {
                 value,
                 style: CommentStyle::Block,
@@ -735,14 +854,24 @@ impl NonCodeNode {
                 let add_start_new_line = if self.start == 0 { "" } else { "\n" };
                 match style {
                     CommentStyle::Block => format!("{}{}/* {} */", add_start_new_line, indentation, value),
-                    CommentStyle::Line => format!("{}{}// {}\n", add_start_new_line, indentation, value),
+                    CommentStyle::Line => format!(
+                        "{}{}// {}\n",
+                        add_start_new_line,
+                        indentation,
+                        line_text(value, options.normalize_comments)
+                    ),
                 }
             }
             NonCodeValue::NewLineBlockComment { value, style } => {
                 let add_start_new_line = if self.start == 0 { "" } else { "\n\n" };
                 match style {
                     CommentStyle::Block => format!("{}{}/* {} */\n", add_start_new_line, indentation, value),
-                    CommentStyle::Line => format!("{}{}// {}\n", add_start_new_line, indentation, value),
+                    CommentStyle::Line => format!(
+                        "{}{}// {}\n",
+                        add_start_new_line,
+                        indentation,
+                        line_text(value, options.normalize_comments)
+                    ),
                 }
             }
             NonCodeValue::NewLine => "\n\n".to_string(),
@@ -896,6 +1025,30 @@ impl CallExpression {
         }
     }
 
+    /// The indices of the arguments that use the `%` of the pipe this call is in, either
+    /// directly or somewhere inside them. A `%` in a nested pipe or function doesn't count,
+    /// since it isn't this pipe's.
+    pub fn substitution_positions(&self) -> Vec<usize> {
+        fn uses_substitution(node: NodeRef<'_>) -> bool {
+            match node {
+                NodeRef::PipeSubstitution(_) => true,
+                NodeRef::PipeExpression(_) | NodeRef::FunctionExpression(_) => false,
+                _ => {
+                    let mut found = false;
+                    node.for_each_child(|child| found |= uses_substitution(child));
+                    found
+                }
+            }
+        }
+
+        self.arguments
+            .iter()
+            .enumerate()
+            .filter(|(_, arg)| uses_substitution((*arg).into()))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
     fn recast(&self, options: &FormatOptions, indentation_level: usize, is_in_pipe: bool) -> String {
         format!(
             "{}({})",
@@ -921,7 +1074,7 @@ impl CallExpression {
 
         for arg in &self.arguments {
             let result: MemoryItem = match arg {
-                Value::Literal(literal) => literal.into(),
+                Value::Literal(literal) => literal.get_result()?,
                 Value::Identifier(identifier) => {
                     let value = memory.get(&identifier.name, identifier.into())?;
                     value.clone()
@@ -968,6 +1121,14 @@ impl CallExpression {
             fn_args.push(result);
         }
 
+        // A pipe stage with no arguments is called with the previous stage's result, as if it
+        // were written `f(%)`, unless the function doesn't take any.
+        if fn_args.is_empty() && pipe_info.is_in_pipe && pipe_info.index > 0 && self.takes_arguments(memory)? {
+            if let Some(previous) = pipe_info.previous_results.get(pipe_info.index - 1) {
+                fn_args.push(previous.clone());
+            }
+        }
+
         match &self.function {
             Function::StdLib { func } => {
                 // Attempt to call the function.
@@ -1007,12 +1168,23 @@ impl CallExpression {
         }
     }
 
+    /// Whether the function this calls has any parameters.
+    fn takes_arguments(&self, memory: &ProgramMemory) -> Result<bool, KclError> {
+        match &self.function {
+            Function::StdLib { func } => Ok(!func.args().is_empty()),
+            Function::InMemory => match memory.get(&self.callee.name, self.into())? {
+                MemoryItem::Function { expression, .. } => Ok(!expression.params.is_empty()),
+                _ => Ok(false),
+            },
+        }
+    }
+
     /// Returns a hover value that includes the given character position.
     pub fn get_hover_value_for_position(&self, pos: usize, code: &str) -> Option<Hover> {
         let callee_source_range: SourceRange = self.callee.clone().into();
         if callee_source_range.contains(pos) {
             return Some(Hover::Function {
-                name: self.callee.name.clone(),
+                name: self.callee.name.to_string(),
                 range: callee_source_range.to_lsp_range(code),
             });
         }
@@ -1021,7 +1193,7 @@ impl CallExpression {
             let source_range: SourceRange = arg.into();
             if source_range.contains(pos) {
                 return Some(Hover::Signature {
-                    name: self.callee.name.clone(),
+                    name: self.callee.name.to_string(),
                     parameter_index: index as u32,
                     range: source_range.to_lsp_range(code),
                 });
@@ -1147,8 +1319,8 @@ impl VariableDeclaration {
         for declaration in &mut self.declarations {
             let declaration_source_range: SourceRange = declaration.id.clone().into();
             if declaration_source_range.contains(pos) {
-                let old_name = declaration.id.name.clone();
-                declaration.id.name = new_name.to_string();
+                let old_name = declaration.id.name.to_string();
+                declaration.id.name = new_name.into();
                 return Some(old_name);
             }
         }
@@ -1189,7 +1361,7 @@ impl VariableDeclaration {
                         let param_source_range: SourceRange = param.into();
                         #[allow(deprecated)]
                         children.push(DocumentSymbol {
-                            name: param.name.clone(),
+                            name: param.name.to_string(),
                             detail: None,
                             kind: SymbolKind::VARIABLE,
                             range: param_source_range.to_lsp_range(code),
@@ -1218,7 +1390,7 @@ impl VariableDeclaration {
 
             #[allow(deprecated)]
             symbols.push(DocumentSymbol {
-                name: declaration.id.name.clone(),
+                name: declaration.id.name.to_string(),
                 detail: Some(self.kind.to_string()),
                 kind: symbol_kind,
                 range: source_range.to_lsp_range(code),
@@ -1306,9 +1478,8 @@ impl VariableDeclarator {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, ts_rs::TS, JsonSchema)]
-#[ts(export)]
-#[serde(tag = "type")]
+/// Serialized with a `kind` next to its `value`, see [`LiteralData`].
+#[derive(Debug, Clone, PartialEq)]
 pub struct Literal {
     pub start: usize,
     pub end: usize,
@@ -1318,6 +1489,218 @@ pub struct Literal {
 
 impl_value_meta!(Literal);
 
+/// What a [`Literal`] is serialized as, which is also its TypeScript type and JSON schema.
+/// `kind` is optional so that literals written without one, by older code or by hand, are
+/// still literals; their `value` is used as it is.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ts_rs::TS, JsonSchema)]
+#[ts(export, rename = "Literal")]
+#[serde(tag = "type", rename = "Literal")]
+struct LiteralData {
+    start: usize,
+    end: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    kind: Option<LiteralKind>,
+    value: serde_json::Value,
+    raw: String,
+}
+
+/// What sort of value a literal holds, so that a string `"5"` and a number `5` can be told
+/// apart without looking at `raw`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ts_rs::TS, JsonSchema)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum LiteralKind {
+    Number,
+    String,
+    Bool,
+    /// A number JSON can't hold, see [`Literal::from_f64`]. Its value is the number's name.
+    SpecialNumber,
+    /// Anything else, which the parser never produces.
+    Json,
+}
+
+/// The value of a literal, typed by its kind. Literals are (de)serialized through this.
+#[derive(Debug, Clone, PartialEq)]
+enum LiteralValue {
+    Number(serde_json::Number),
+    String(String),
+    Bool(bool),
+    SpecialNumber(SpecialNumber),
+    Json(serde_json::Value),
+}
+
+impl LiteralValue {
+    /// The value a literal of `kind` serialized as `value` has, if it can have it.
+    fn from_kind(kind: LiteralKind, value: serde_json::Value) -> Result<Self, String> {
+        Ok(match (kind, value) {
+            (LiteralKind::Number, serde_json::Value::Number(n)) => LiteralValue::Number(n),
+            (LiteralKind::String, serde_json::Value::String(s)) => LiteralValue::String(s),
+            (LiteralKind::Bool, serde_json::Value::Bool(b)) => LiteralValue::Bool(b),
+            (LiteralKind::SpecialNumber, value) => {
+                LiteralValue::SpecialNumber(serde_json::from_value(value).map_err(|e| e.to_string())?)
+            }
+            (LiteralKind::Json, value) => LiteralValue::Json(value),
+            (kind, value) => return Err(format!("a literal of kind {:?} can't have the value {}", kind, value)),
+        })
+    }
+
+    fn kind(&self) -> LiteralKind {
+        match self {
+            LiteralValue::Number(_) => LiteralKind::Number,
+            LiteralValue::String(_) => LiteralKind::String,
+            LiteralValue::Bool(_) => LiteralKind::Bool,
+            LiteralValue::SpecialNumber(_) => LiteralKind::SpecialNumber,
+            LiteralValue::Json(_) => LiteralKind::Json,
+        }
+    }
+
+    /// The value as it's serialized, next to its kind.
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            LiteralValue::SpecialNumber(special) => special.name().into(),
+            other => other.clone().into(),
+        }
+    }
+}
+
+/// A floating point value that isn't a JSON number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ts_rs::TS, JsonSchema)]
+#[ts(export)]
+pub enum SpecialNumber {
+    Infinity,
+    #[serde(rename = "-Infinity")]
+    NegativeInfinity,
+    NaN,
+}
+
+impl SpecialNumber {
+    /// How the number is written, which is also how it's serialized.
+    pub fn name(self) -> &'static str {
+        match self {
+            SpecialNumber::Infinity => "Infinity",
+            SpecialNumber::NegativeInfinity => "-Infinity",
+            SpecialNumber::NaN => "NaN",
+        }
+    }
+
+    pub fn as_f64(self) -> f64 {
+        match self {
+            SpecialNumber::Infinity => f64::INFINITY,
+            SpecialNumber::NegativeInfinity => f64::NEG_INFINITY,
+            SpecialNumber::NaN => f64::NAN,
+        }
+    }
+}
+
+impl From<serde_json::Value> for LiteralValue {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Number(n) => LiteralValue::Number(n),
+            serde_json::Value::String(s) => LiteralValue::String(s),
+            serde_json::Value::Bool(b) => LiteralValue::Bool(b),
+            other => LiteralValue::Json(other),
+        }
+    }
+}
+
+impl From<LiteralValue> for serde_json::Value {
+    fn from(value: LiteralValue) -> Self {
+        match value {
+            LiteralValue::Number(n) => serde_json::Value::Number(n),
+            LiteralValue::String(s) => serde_json::Value::String(s),
+            LiteralValue::Bool(b) => serde_json::Value::Bool(b),
+            LiteralValue::SpecialNumber(_) => serde_json::Value::Null,
+            LiteralValue::Json(other) => other,
+        }
+    }
+}
+
+impl Serialize for Literal {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let value = match self.special_number() {
+            Some(special) => LiteralValue::SpecialNumber(special),
+            None => self.value.clone().into(),
+        };
+        LiteralData {
+            start: self.start,
+            end: self.end,
+            kind: Some(value.kind()),
+            value: value.to_json(),
+            raw: self.raw.clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Literal {
+    fn deserialize<D>(deserializer: D) -> Result<Literal, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let data = LiteralData::deserialize(deserializer)?;
+        let mut raw = data.raw;
+        let value = match data.kind {
+            Some(kind) => {
+                let value = LiteralValue::from_kind(kind, data.value).map_err(serde::de::Error::custom)?;
+                // A special number is told apart by its raw, so that has to be its name, whatever
+                // was written there.
+                if let LiteralValue::SpecialNumber(special) = &value {
+                    raw = special.name().to_owned();
+                }
+                value.into()
+            }
+            None => data.value,
+        };
+        Ok(Literal {
+            start: data.start,
+            end: data.end,
+            value,
+            raw,
+        })
+    }
+}
+
+impl JsonSchema for Literal {
+    fn schema_name() -> String {
+        LiteralData::schema_name()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        LiteralData::json_schema(gen)
+    }
+}
+
+impl ts_rs::TS for Literal {
+    const EXPORT_TO: Option<&'static str> = LiteralData::EXPORT_TO;
+
+    fn name() -> String {
+        LiteralData::name()
+    }
+
+    fn decl() -> String {
+        LiteralData::decl()
+    }
+
+    fn inline() -> String {
+        LiteralData::inline()
+    }
+
+    fn dependencies() -> Vec<ts_rs::Dependency>
+    where
+        Self: 'static,
+    {
+        LiteralData::dependencies()
+    }
+
+    fn transparent() -> bool {
+        LiteralData::transparent()
+    }
+}
+
 impl From<Literal> for Value {
     fn from(literal: Literal) -> Self {
         Value::Literal(Box::new(literal))
@@ -1334,6 +1717,57 @@ impl Literal {
         }
     }
 
+    /// A number literal for `value`. JSON numbers can't be infinite or NaN, so those have a null
+    /// `value` and their name (like `Infinity`) as `raw`, and serialize with the kind
+    /// `specialNumber`. They recast as arithmetic that works them out, like `(1 / 0)`, but
+    /// can't be executed as they are, see [`Literal::get_result`].
+    pub fn from_f64(value: f64) -> Self {
+        let special = if value.is_nan() {
+            SpecialNumber::NaN
+        } else if value == f64::INFINITY {
+            SpecialNumber::Infinity
+        } else if value == f64::NEG_INFINITY {
+            SpecialNumber::NegativeInfinity
+        } else {
+            return Self::new(value.into());
+        };
+        Self {
+            start: 0,
+            end: 0,
+            value: serde_json::Value::Null,
+            raw: special.name().to_owned(),
+        }
+    }
+
+    /// The infinity or NaN this literal stands for, if it's one made by [`Literal::from_f64`].
+    pub fn special_number(&self) -> Option<SpecialNumber> {
+        if !self.value.is_null() {
+            return None;
+        }
+        [
+            SpecialNumber::Infinity,
+            SpecialNumber::NegativeInfinity,
+            SpecialNumber::NaN,
+        ]
+        .into_iter()
+        .find(|special| special.name() == self.raw)
+    }
+
+    /// The value this literal evaluates to. Memory holds JSON, which has no infinity or NaN, so a
+    /// special number is an error here rather than quietly becoming null.
+    pub fn get_result(&self) -> Result<MemoryItem, KclError> {
+        if let Some(special) = self.special_number() {
+            return Err(KclError::Semantic(KclErrorDetails {
+                source_ranges: vec![self.into()],
+                message: format!(
+                    "{} can't be used as a value, KCL numbers must be finite",
+                    special.name()
+                ),
+            }));
+        }
+        Ok(self.clone().into())
+    }
+
     /// Get the constraint level for this literal.
     /// Literals are always not constrained.
     pub fn get_constraint_level(&self) -> ConstraintLevel {
@@ -1342,16 +1776,48 @@ impl Literal {
         }
     }
 
-    fn recast(&self) -> String {
+    fn recast(&self, options: &FormatOptions) -> String {
         if let serde_json::Value::String(value) = &self.value {
-            let quote = if self.raw.trim().starts_with('"') { '"' } else { '\'' };
+            let written = if self.raw.trim().starts_with('"') { '"' } else { '\'' };
+            let quote = match options.quote_style {
+                QuoteStyle::Preserve => written,
+                QuoteStyle::Single if can_quote(value, '\'') => '\'',
+                QuoteStyle::Double if can_quote(value, '"') => '"',
+                QuoteStyle::Single => '"',
+                QuoteStyle::Double => '\'',
+            };
             format!("{}{}{}", quote, value, quote)
+        } else if let Some(special) = self.special_number() {
+            // KCL has no way to write these as numbers, so write arithmetic that works them out,
+            // in parens so it's still one operand wherever the literal was.
+            match special {
+                SpecialNumber::Infinity => "(1 / 0)",
+                SpecialNumber::NegativeInfinity => "(-1 / 0)",
+                SpecialNumber::NaN => "(0 / 0)",
+            }
+            .to_owned()
         } else {
             self.value.to_string()
         }
     }
 }
 
+/// Whether the text of a string can be written between `quote`s, which it can if every `quote`
+/// in it is escaped.
+fn can_quote(value: &str, quote: char) -> bool {
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return false;
+        }
+    }
+    true
+}
+
 impl From<Literal> for MemoryItem {
     fn from(literal: Literal) -> Self {
         MemoryItem::UserVal(UserVal {
@@ -1374,13 +1840,22 @@ impl From<&Box<Literal>> for MemoryItem {
     }
 }
 
+/// What [`Identifier::name`] is: an interned [`Symbol`](crate::ast::intern::Symbol) with the
+/// `intern` feature, and a plain `String` without it. Both serialize as the name.
+#[cfg(not(feature = "intern"))]
+pub type Name = String;
+#[cfg(feature = "intern")]
+pub type Name = crate::ast::intern::Symbol;
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, ts_rs::TS, JsonSchema)]
 #[ts(export)]
 #[serde(tag = "type")]
 pub struct Identifier {
     pub start: usize,
     pub end: usize,
-    pub name: String,
+    #[ts(type = "string")]
+    #[schemars(with = "String")]
+    pub name: Name,
 }
 
 impl_value_meta!(Identifier);
@@ -1390,7 +1865,7 @@ impl Identifier {
         Self {
             start: 0,
             end: 0,
-            name: name.to_string(),
+            name: name.into(),
         }
     }
 
@@ -1405,7 +1880,7 @@ impl Identifier {
     /// Rename all identifiers that have the old name to the new given name.
     fn rename(&mut self, old_name: &str, new_name: &str) {
         if self.name == old_name {
-            self.name = new_name.to_string();
+            self.name = new_name.into();
         }
     }
 }
@@ -1445,6 +1920,12 @@ pub struct ArrayExpression {
     pub start: usize,
     pub end: usize,
     pub elements: Vec<Value>,
+    /// Where the source broke lines: the indices of the elements that started a line, then
+    /// `elements.len()` if the closing bracket did. `None` if it didn't break any. See
+    /// [`FormatOptions::preserve_collection_breaks`].
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "lineBreaks")]
+    #[ts(optional)]
+    pub line_breaks: Option<Vec<usize>>,
 }
 
 impl_value_meta!(ArrayExpression);
@@ -1461,6 +1942,7 @@ impl ArrayExpression {
             start: 0,
             end: 0,
             elements,
+            line_breaks: None,
         }
     }
 
@@ -1486,6 +1968,26 @@ impl ArrayExpression {
     }
 
     fn recast(&self, options: &FormatOptions, indentation_level: usize, is_in_pipe: bool) -> String {
+        if let Some(line_breaks) = self
+            .line_breaks
+            .as_ref()
+            .filter(|line_breaks| options.preserve_collection_breaks && !line_breaks.is_empty())
+        {
+            let elements: Vec<String> = self
+                .elements
+                .iter()
+                .map(|el| el.recast(options, indentation_level + 1, is_in_pipe))
+                .collect();
+            return recast_with_line_breaks(
+                ("[", "", "]"),
+                &elements,
+                line_breaks,
+                options,
+                indentation_level,
+                is_in_pipe,
+            );
+        }
+
         let flat_recast = format!(
             "[{}]",
             self.elements
@@ -1543,7 +2045,7 @@ impl ArrayExpression {
 
         for element in &self.elements {
             let result = match element {
-                Value::Literal(literal) => literal.into(),
+                Value::Literal(literal) => literal.get_result()?,
                 Value::Identifier(identifier) => {
                     let value = memory.get(&identifier.name, identifier.into())?;
                     value.clone()
@@ -1598,6 +2100,50 @@ impl ArrayExpression {
     }
 }
 
+/// Write the items of an array or object with a line break before each item in
+/// `line_breaks`, and before the closing bracket if `line_breaks` has `items.len()`.
+/// `pad` goes between a bracket and an item on the same line.
+fn recast_with_line_breaks(
+    (open, pad, close): (&str, &str, &str),
+    items: &[String],
+    line_breaks: &[usize],
+    options: &FormatOptions,
+    indentation_level: usize,
+    is_in_pipe: bool,
+) -> String {
+    let indentation = |level| {
+        if is_in_pipe {
+            options.get_indentation_offset_pipe(level)
+        } else {
+            options.get_indentation(level)
+        }
+    };
+
+    let mut result = open.to_string();
+    for (index, item) in items.iter().enumerate() {
+        if line_breaks.contains(&index) {
+            result.push('\n');
+            result.push_str(&indentation(indentation_level + 1));
+        } else if index == 0 {
+            result.push_str(pad);
+        } else {
+            result.push(' ');
+        }
+        result.push_str(item);
+        if index + 1 < items.len() {
+            result.push(',');
+        }
+    }
+    if line_breaks.contains(&items.len()) {
+        result.push('\n');
+        result.push_str(&indentation(indentation_level));
+    } else {
+        result.push_str(pad);
+    }
+    result.push_str(close);
+    result
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, ts_rs::TS, JsonSchema)]
 #[ts(export)]
 #[serde(tag = "type")]
@@ -1605,6 +2151,10 @@ pub struct ObjectExpression {
     pub start: usize,
     pub end: usize,
     pub properties: Vec<ObjectProperty>,
+    /// Where the source broke lines, like [`ArrayExpression::line_breaks`].
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "lineBreaks")]
+    #[ts(optional)]
+    pub line_breaks: Option<Vec<usize>>,
 }
 
 impl ObjectExpression {
@@ -1613,6 +2163,7 @@ impl ObjectExpression {
             start: 0,
             end: 0,
             properties,
+            line_breaks: None,
         }
     }
 
@@ -1638,6 +2189,29 @@ impl ObjectExpression {
     }
 
     fn recast(&self, options: &FormatOptions, indentation_level: usize, is_in_pipe: bool) -> String {
+        if let Some(line_breaks) = self
+            .line_breaks
+            .as_ref()
+            .filter(|line_breaks| options.preserve_collection_breaks && !line_breaks.is_empty())
+        {
+            let properties: Vec<String> = self
+                .properties
+                .iter()
+                .map(|prop| {
+                    let value = prop.value.recast(options, indentation_level + 1, is_in_pipe);
+                    format!("{}: {}", prop.key.name, value)
+                })
+                .collect();
+            return recast_with_line_breaks(
+                ("{", " ", "}"),
+                &properties,
+                line_breaks,
+                options,
+                indentation_level,
+                is_in_pipe,
+            );
+        }
+
         let flat_recast = format!(
             "{{ {} }}",
             self.properties
@@ -1694,7 +2268,7 @@ impl ObjectExpression {
         let mut object = Map::new();
         for property in &self.properties {
             let result = match &property.value {
-                Value::Literal(literal) => literal.into(),
+                Value::Literal(literal) => literal.get_result()?,
                 Value::Identifier(identifier) => {
                     let value = memory.get(&identifier.name, identifier.into())?;
                     value.clone()
@@ -1734,7 +2308,7 @@ impl ObjectExpression {
                 }
             };
 
-            object.insert(property.key.name.clone(), result.get_json_value()?);
+            object.insert(property.key.name.to_string(), result.get_json_value()?);
         }
 
         Ok(MemoryItem::UserVal(UserVal {
@@ -1929,7 +2503,9 @@ impl MemberExpression {
         None
     }
 
-    pub fn get_result_array(&self, memory: &mut ProgramMemory, index: usize) -> Result<MemoryItem, KclError> {
+    /// The element at `index` of the array this expression indexes into. A negative index
+    /// counts back from the end, so `-1` is the last element.
+    pub fn get_result_array(&self, memory: &mut ProgramMemory, index: i64) -> Result<MemoryItem, KclError> {
         let array = match &self.object {
             MemberObject::MemberExpression(member_expr) => member_expr.get_result(memory)?,
             MemberObject::Identifier(identifier) => {
@@ -1941,7 +2517,12 @@ impl MemberExpression {
         let array_json = array.get_json_value()?;
 
         if let serde_json::Value::Array(array) = array_json {
-            if let Some(value) = array.get(index) {
+            let position = if index < 0 {
+                array.len().checked_sub(index.unsigned_abs() as usize)
+            } else {
+                Some(index as usize)
+            };
+            if let Some(value) = position.and_then(|position| array.get(position)) {
                 Ok(MemoryItem::UserVal(UserVal {
                     value: value.clone(),
                     meta: vec![Metadata {
@@ -1970,9 +2551,15 @@ impl MemberExpression {
                 // Parse this as a string.
                 if let serde_json::Value::String(string) = value {
                     string
-                } else if let serde_json::Value::Number(_) = &value {
+                } else if let serde_json::Value::Number(number) = &value {
                     // It can also be a number if we are getting a member of an array.
-                    return self.get_result_array(memory, parse_json_number_as_usize(&value, self.into())?);
+                    let index = number.as_i64().ok_or_else(|| {
+                        KclError::Syntax(KclErrorDetails {
+                            source_ranges: vec![literal.into()],
+                            message: format!("Invalid index: {}", value),
+                        })
+                    })?;
+                    return self.get_result_array(memory, index);
                 } else {
                     return Err(KclError::Semantic(KclErrorDetails {
                         message: format!("Expected string literal or number for property name, found {:?}", value),
@@ -2047,6 +2634,11 @@ pub struct BinaryExpression {
     pub operator: BinaryOperator,
     pub left: BinaryPart,
     pub right: BinaryPart,
+    /// `Some(true)` if the source wrote this expression in parens, which the parser otherwise
+    /// drops. Left out of expressions built in code. Only [`ParenPolicy::Preserve`] reads it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[ts(optional)]
+    pub parenthesized: Option<bool>,
 }
 
 impl_value_meta!(BinaryExpression);
@@ -2059,9 +2651,15 @@ impl BinaryExpression {
             operator,
             left,
             right,
+            parenthesized: None,
         }
     }
 
+    /// Whether the source wrote this expression in parens.
+    pub fn is_parenthesized(&self) -> bool {
+        self.parenthesized == Some(true)
+    }
+
     pub fn replace_value(&mut self, source_range: SourceRange, new_value: Value) {
         self.left.replace_value(source_range, new_value.clone());
         self.right.replace_value(source_range, new_value);
@@ -2090,26 +2688,50 @@ impl BinaryExpression {
             }
         };
 
+        // Operators are left associative, so a right operand of the same precedence as ours
+        // parses back grouped differently without parens. That only changes the result after
+        // `-`, `/` and `%`, or for a `%` after `*`: `a * (b % c)` isn't `a * b % c`.
         let should_wrap_right = match &self.right {
-            BinaryPart::BinaryExpression(bin_exp) => {
-                self.precedence() > bin_exp.precedence()
-                    || self.operator == BinaryOperator::Sub
-                    || self.operator == BinaryOperator::Div
-            }
+            BinaryPart::BinaryExpression(bin_exp) if !bin_exp.wraps_itself(options) => match options.parens {
+                ParenPolicy::Minimal | ParenPolicy::Preserve => {
+                    self.precedence() > bin_exp.precedence()
+                        || matches!(
+                            self.operator,
+                            BinaryOperator::Sub | BinaryOperator::Div | BinaryOperator::Mod
+                        )
+                        || (self.precedence() == bin_exp.precedence() && bin_exp.operator == BinaryOperator::Mod)
+                }
+                ParenPolicy::Defensive => true,
+                ParenPolicy::Full => false,
+            },
             _ => false,
         };
 
         let should_wrap_left = match &self.left {
-            BinaryPart::BinaryExpression(bin_exp) => self.precedence() > bin_exp.precedence(),
+            BinaryPart::BinaryExpression(bin_exp) if !bin_exp.wraps_itself(options) => match options.parens {
+                ParenPolicy::Minimal | ParenPolicy::Preserve => self.precedence() > bin_exp.precedence(),
+                ParenPolicy::Defensive => true,
+                ParenPolicy::Full => false,
+            },
             _ => false,
         };
 
-        format!(
+        let recast = format!(
             "{} {} {}",
             maybe_wrap_it(self.left.recast(options, 0), should_wrap_left),
             self.operator,
             maybe_wrap_it(self.right.recast(options, 0), should_wrap_right)
-        )
+        );
+        maybe_wrap_it(recast, self.wraps_itself(options))
+    }
+
+    /// Whether this expression writes its own parens, so whatever it's in mustn't add more.
+    fn wraps_itself(&self, options: &FormatOptions) -> bool {
+        match options.parens {
+            ParenPolicy::Full => true,
+            ParenPolicy::Preserve => self.is_parenthesized(),
+            ParenPolicy::Minimal | ParenPolicy::Defensive => false,
+        }
     }
 
     /// Returns a hover value that includes the given character position.
@@ -2232,7 +2854,7 @@ pub fn parse_json_value_as_string(j: &serde_json::Value) -> Option<String> {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, ts_rs::TS, JsonSchema, FromStr, Display)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash, ts_rs::TS, JsonSchema, FromStr, Display)]
 #[ts(export)]
 #[serde(rename_all = "snake_case")]
 #[display(style = "snake_case")]
@@ -2298,7 +2920,14 @@ impl UnaryExpression {
     }
 
     fn recast(&self, options: &FormatOptions) -> String {
-        format!("{}{}", &self.operator, self.argument.recast(options, 0))
+        // A unary operator binds tighter than any binary one, so a binary argument always needs
+        // parens, unless it writes its own.
+        match &self.argument {
+            BinaryPart::BinaryExpression(binary) if !binary.wraps_itself(options) => {
+                format!("{}({})", &self.operator, self.argument.recast(options, 0))
+            }
+            _ => format!("{}{}", &self.operator, self.argument.recast(options, 0)),
+        }
     }
 
     pub async fn get_result(
@@ -2345,7 +2974,7 @@ impl UnaryExpression {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, ts_rs::TS, JsonSchema, FromStr, Display)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash, ts_rs::TS, JsonSchema, FromStr, Display)]
 #[ts(export)]
 #[serde(rename_all = "snake_case")]
 #[display(style = "snake_case")]
@@ -2388,6 +3017,26 @@ impl PipeExpression {
         }
     }
 
+    /// The stage at `index`, if the pipe has that many stages.
+    pub fn stage(&self, index: usize) -> Option<&Value> {
+        self.body.get(index)
+    }
+
+    /// The stage at `index`, if the pipe has that many stages.
+    pub fn stage_mut(&mut self, index: usize) -> Option<&mut Value> {
+        self.body.get_mut(index)
+    }
+
+    /// The value the pipe starts from.
+    pub fn first_stage(&self) -> Option<&Value> {
+        self.body.first()
+    }
+
+    /// The stage whose result is the result of the whole pipe.
+    pub fn last_stage(&self) -> Option<&Value> {
+        self.body.last()
+    }
+
     pub fn replace_value(&mut self, source_range: SourceRange, new_value: Value) {
         for value in &mut self.body {
             value.replace_value(source_range, new_value.clone());
@@ -2416,11 +3065,20 @@ impl PipeExpression {
             .enumerate()
             .map(|(index, statement)| {
                 let indentation = options.get_indentation(indentation_level + 1);
-                let mut s = statement.recast(options, indentation_level + 1, true);
+                let mut s = match statement {
+                    Value::CallExpression(call)
+                        if index > 0
+                            && !options.explicit_substitution
+                            && matches!(call.arguments[..], [Value::PipeSubstitution(_)]) =>
+                    {
+                        format!("{}()", call.callee.name)
+                    }
+                    _ => statement.recast(options, indentation_level + 1, true),
+                };
                 let non_code_meta = self.non_code_meta.clone();
                 if let Some(non_code_meta_value) = non_code_meta.non_code_nodes.get(&index) {
                     for val in non_code_meta_value {
-                        s += val.format(&indentation).trim_end_matches('\n')
+                        s += val.format(&indentation, options).trim_end_matches('\n')
                     }
                 }
 
@@ -2550,7 +3208,7 @@ impl FunctionExpression {
             "({}) => {{\n{}{}\n}}",
             self.params
                 .iter()
-                .map(|param| param.name.clone())
+                .map(|param| param.name.to_string())
                 .collect::<Vec<String>>()
                 .join(", "),
             options.get_indentation(indentation_level + 1),
@@ -2595,6 +3253,8 @@ pub enum Hover {
 }
 
 /// Format options.
+/// Trailing commas and pipe layout aren't options: a trailing comma is never written, and each
+/// stage of a pipe always goes on its own line.
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, ts_rs::TS, JsonSchema)]
 #[ts(export)]
 #[serde(rename_all = "camelCase")]
@@ -2607,6 +3267,33 @@ pub struct FormatOptions {
     /// If true, ensure file ends with a newline.
     /// If false, ensure file does not end with a newline.
     pub insert_final_newline: bool,
+    /// Whether statements end with a semicolon.
+    #[serde(default)]
+    pub semicolons: SemicolonPolicy,
+    /// Which parens to write around the operands of binary expressions.
+    #[serde(default)]
+    pub parens: ParenPolicy,
+    /// Trim the whitespace around the text of `//` comments, so there's exactly one space after
+    /// the `//` and none at the end of the line. Block comments are left as they are.
+    /// The parser already does this for comments it reads, so this matters for ASTs which were
+    /// built or edited somewhere else.
+    #[serde(default)]
+    pub normalize_comments: bool,
+    /// Break arrays and objects across lines where the source did, instead of deciding from
+    /// how long they are. Arrays and objects written on one line are laid out as usual.
+    #[serde(default)]
+    pub preserve_collection_breaks: bool,
+    /// Write the `%` of a pipe stage call whose only argument it is. When false, `|> close(%)`
+    /// is written `|> close()`, which the executor runs the same way.
+    #[serde(default = "default_explicit_substitution")]
+    pub explicit_substitution: bool,
+    /// Which quotes strings are written in.
+    #[serde(default)]
+    pub quote_style: QuoteStyle,
+}
+
+fn default_explicit_substitution() -> bool {
+    true
 }
 
 impl Default for FormatOptions {
@@ -2623,6 +3310,47 @@ impl FormatOptions {
             tab_size: 2,
             use_tabs: false,
             insert_final_newline: true,
+            semicolons: SemicolonPolicy::Never,
+            parens: ParenPolicy::Minimal,
+            normalize_comments: false,
+            preserve_collection_breaks: false,
+            explicit_substitution: true,
+            quote_style: QuoteStyle::Preserve,
+        }
+    }
+
+    /// For code people edit by hand: the defaults, but keeping the parens the user wrote and
+    /// the line breaks in their arrays and objects, and tidying the spacing of comments.
+    pub fn default_pretty() -> Self {
+        Self {
+            parens: ParenPolicy::Preserve,
+            normalize_comments: true,
+            preserve_collection_breaks: true,
+            ..Self::new()
+        }
+    }
+
+    /// As few characters as possible without changing the layout rules: one tab per level of
+    /// indentation, only the parens that are needed, tidied comments, and no newline at the end
+    /// of the file.
+    pub fn compact() -> Self {
+        Self {
+            use_tabs: true,
+            insert_final_newline: false,
+            normalize_comments: true,
+            ..Self::new()
+        }
+    }
+
+    /// Output that depends only on the AST, not on how the source was laid out, so two
+    /// programs that mean the same thing recast to the same text. That's the defaults, with
+    /// comments tidied and strings in single quotes: two spaces per level, minimal parens,
+    /// layout decided by length, no semicolons, `%` always written, and a final newline.
+    pub fn canonical() -> Self {
+        Self {
+            normalize_comments: true,
+            quote_style: QuoteStyle::Single,
+            ..Self::new()
         }
     }
 
@@ -2646,6 +3374,52 @@ impl FormatOptions {
     }
 }
 
+/// Whether the recaster ends statements with a semicolon.
+/// The parser accepts statements with or without one either way.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, ts_rs::TS, JsonSchema)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum SemicolonPolicy {
+    /// Never write a semicolon.
+    #[default]
+    Never,
+    /// End every statement with a semicolon.
+    Always,
+}
+
+/// Which quotes the recaster writes strings in.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, ts_rs::TS, JsonSchema)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum QuoteStyle {
+    /// The quotes each string was written with.
+    #[default]
+    Preserve,
+    /// Single quotes, unless the string has a `'` in it that isn't escaped.
+    Single,
+    /// Double quotes, unless the string has a `"` in it that isn't escaped.
+    Double,
+}
+
+/// Which parens the recaster writes around the operands of binary expressions.
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, ts_rs::TS, JsonSchema)]
+#[ts(export)]
+#[serde(rename_all = "camelCase")]
+pub enum ParenPolicy {
+    /// Only the parens needed to keep the meaning of the expression.
+    #[default]
+    Minimal,
+    /// The parens the user wrote around binary expressions, plus any that are needed.
+    Preserve,
+    /// Parens around every operand that is itself a binary expression, so nested arithmetic
+    /// never relies on precedence. Other operands (calls, member expressions, unary
+    /// expressions, literals and names) are never wrapped, since nothing binds tighter than them.
+    Defensive,
+    /// Parens around every binary expression, including the outermost one, so the grouping
+    /// can be read without knowing the precedence of the operators.
+    Full,
+}
+
 /// The constraint level.
 #[derive(Debug, Clone, Deserialize, Serialize, ts_rs::TS, JsonSchema, Display)]
 #[ts(export)]
@@ -3120,6 +3894,7 @@ const part001 = startSketchOn('XY')
                 tab_size: 3,
                 use_tabs: false,
                 insert_final_newline: true,
+                ..Default::default()
             },
             0,
         );
@@ -3255,4 +4030,624 @@ const thickness = sqrt(distance * p * FOS * 6 / (sigmaAllow * width))"#;
         let recasted = program.recast(&Default::default(), 0);
         assert_eq!(recasted.trim(), some_program_string);
     }
+
+    #[test]
+    fn test_recast_semicolons() {
+        let with_semicolons = r#"const width = 10;
+fn double = (x) => {
+  return x * 2;
+}
+show(double(width)); // done
+"#;
+        let without_semicolons = r#"const width = 10
+fn double = (x) => {
+  return x * 2
+}
+show(double(width)) // done
+"#;
+        let always = FormatOptions {
+            semicolons: SemicolonPolicy::Always,
+            ..Default::default()
+        };
+
+        for code in [with_semicolons, without_semicolons] {
+            let tokens = crate::token::lexer(code);
+            let program = crate::parser::Parser::new(tokens).ast().unwrap();
+            assert_eq!(program.recast(&Default::default(), 0), without_semicolons);
+            assert_eq!(program.recast(&always, 0), with_semicolons);
+        }
+    }
+
+    #[test]
+    fn test_recast_paren_policy() {
+        let code = "const x = (a + b) * c\nconst y = (a * b) + c\nconst z = 1 - (b - c)";
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+        let recast = |parens| {
+            program.recast(
+                &FormatOptions {
+                    parens,
+                    ..Default::default()
+                },
+                0,
+            )
+        };
+
+        assert_eq!(
+            recast(ParenPolicy::Minimal),
+            "const x = (a + b) * c\nconst y = a * b + c\nconst z = 1 - (b - c)\n"
+        );
+        assert_eq!(
+            recast(ParenPolicy::Preserve),
+            "const x = (a + b) * c\nconst y = (a * b) + c\nconst z = 1 - (b - c)\n"
+        );
+        assert_eq!(
+            recast(ParenPolicy::Defensive),
+            "const x = (a + b) * c\nconst y = (a * b) + c\nconst z = 1 - (b - c)\n"
+        );
+        assert_eq!(
+            recast(ParenPolicy::Full),
+            "const x = ((a + b) * c)\nconst y = ((a * b) + c)\nconst z = (1 - (b - c))\n"
+        );
+    }
+
+    #[test]
+    fn test_recast_preserve_reads_parenthesized() {
+        let code = "const x = (a * b) + c";
+        let tokens = crate::token::lexer(code);
+        let mut program = crate::parser::Parser::new(tokens).ast().unwrap();
+        let preserve = FormatOptions {
+            parens: ParenPolicy::Preserve,
+            ..Default::default()
+        };
+        assert_eq!(program.recast(&preserve, 0), format!("{}\n", code));
+
+        // The parser records the parens on the operand; its span doesn't come into it.
+        let BodyItem::VariableDeclaration(declaration) = &mut program.body[0] else {
+            panic!("expected a declaration");
+        };
+        let Value::BinaryExpression(binary) = &mut declaration.declarations[0].init else {
+            panic!("expected a binary expression");
+        };
+        let BinaryPart::BinaryExpression(left) = &mut binary.left else {
+            panic!("expected a binary expression on the left");
+        };
+        assert!(left.is_parenthesized());
+        left.parenthesized = None;
+        assert_eq!(program.recast(&preserve, 0), "const x = a * b + c\n");
+    }
+
+    #[test]
+    fn test_recast_right_operand_same_precedence() {
+        // A `%` used to lose its parens unless the outer operator was `-` or `/`, which
+        // regrouped it: `a * (b % c)` came out as `a * b % c`, which is `(a * b) % c`.
+        let code = "const x = a * (b % c)\nconst y = a % (b * c)\nconst z = a - (b + c)";
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+        let recast = program.recast(&Default::default(), 0);
+        assert_eq!(recast, format!("{}\n", code));
+
+        let reparsed = crate::parser::Parser::new(crate::token::lexer(&recast)).ast().unwrap();
+        assert!(crate::ast::walk::ast_eq(&program, &reparsed));
+
+        // Regrouping these doesn't change the result, so the parens aren't needed.
+        let code = "const x = a + (b + c)\nconst y = a * (b / c)";
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "const x = a + b + c\nconst y = a * b / c\n"
+        );
+
+        // Operands on the left don't need them.
+        let code = "const x = a + b + c\nconst y = a * b % c";
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+        assert_eq!(program.recast(&Default::default(), 0), format!("{}\n", code));
+    }
+
+    #[test]
+    fn test_recast_unary_binary_argument() {
+        let code = "const x = -(a * b)\nconst y = -a * b";
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+        assert_eq!(program.recast(&Default::default(), 0), format!("{}\n", code));
+    }
+
+    #[test]
+    fn test_recast_full_parens() {
+        let code = "const x = a + b * c\nconst y = -(d - e * f)\nconst z = -a * b";
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+        let options = FormatOptions {
+            parens: ParenPolicy::Full,
+            ..Default::default()
+        };
+        let recast = program.recast(&options, 0);
+        assert_eq!(
+            recast,
+            "const x = (a + (b * c))\nconst y = -(d - (e * f))\nconst z = (-a * b)\n"
+        );
+
+        // The extra parens don't change what the program means.
+        let reparsed = crate::parser::Parser::new(crate::token::lexer(&recast)).ast().unwrap();
+        assert!(crate::ast::walk::ast_eq(&program, &reparsed));
+    }
+
+    #[test]
+    fn test_recast_special_numbers() {
+        let tokens = crate::token::lexer("show(a, b, c * 2)");
+        let mut program = crate::parser::Parser::new(tokens).ast().unwrap();
+        let BodyItem::ExpressionStatement(statement) = &mut program.body[0] else {
+            panic!("expected an expression statement");
+        };
+        let Value::CallExpression(call) = &mut statement.expression else {
+            panic!("expected a call expression");
+        };
+        call.arguments[0] = Literal::from_f64(f64::INFINITY).into();
+        call.arguments[1] = Literal::from_f64(f64::NEG_INFINITY).into();
+        let Value::BinaryExpression(binary) = &mut call.arguments[2] else {
+            panic!("expected a binary expression");
+        };
+        binary.left = BinaryPart::Literal(Box::new(Literal::from_f64(f64::NAN)));
+
+        let recast = program.recast(&Default::default(), 0);
+        assert_eq!(recast, "show((1 / 0), (-1 / 0), (0 / 0) * 2)\n");
+        // The arithmetic parses back, and isn't folded away since the results aren't finite.
+        let mut reparsed = crate::parser::Parser::new(crate::token::lexer(&recast)).ast().unwrap();
+        crate::ast::transform::fold_constants(&mut reparsed);
+        assert_eq!(
+            reparsed.recast(&Default::default(), 0),
+            "show(1 / 0, -1 / 0, 0 / 0 * 2)\n"
+        );
+    }
+
+    #[test]
+    fn test_literal_serde_kind() {
+        let string = Literal::new(serde_json::json!("5"));
+        let number = Literal::new(serde_json::json!(5));
+
+        let string_json = serde_json::to_value(&string).unwrap();
+        let number_json = serde_json::to_value(&number).unwrap();
+        assert_eq!(string_json["kind"], "string");
+        assert_eq!(number_json["kind"], "number");
+        // The value itself is where it always was.
+        assert_eq!(string_json["value"], "5");
+        assert_eq!(number_json["value"], 5);
+        assert_ne!(string_json, number_json);
+
+        assert_eq!(serde_json::from_value::<Literal>(string_json).unwrap(), string);
+        assert_eq!(serde_json::from_value::<Literal>(number_json).unwrap(), number);
+
+        // A literal without a kind still deserializes.
+        let old = serde_json::json!({ "type": "Literal", "start": 0, "end": 1, "value": 5, "raw": "5" });
+        assert_eq!(
+            serde_json::from_value::<Literal>(old).unwrap().value,
+            serde_json::json!(5)
+        );
+
+        // A kind that doesn't match the value is an error.
+        let mismatched = serde_json::json!({ "type": "Literal", "start": 0, "end": 3, "kind": "number", "value": "5", "raw": "'5'" });
+        assert!(serde_json::from_value::<Literal>(mismatched).is_err());
+    }
+
+    #[test]
+    fn test_literal_serde_special_number() {
+        let infinity = Literal::from_f64(f64::INFINITY);
+        assert_eq!(infinity.special_number(), Some(SpecialNumber::Infinity));
+
+        let json = serde_json::to_value(&infinity).unwrap();
+        assert_eq!(json["kind"], "specialNumber");
+        assert_eq!(json["value"], "Infinity");
+        let back = serde_json::from_value::<Literal>(json).unwrap();
+        assert_eq!(back, infinity);
+        assert_eq!(back.special_number().map(SpecialNumber::as_f64), Some(f64::INFINITY));
+
+        let nan = serde_json::to_string(&Literal::from_f64(f64::NAN)).unwrap();
+        let back = serde_json::from_str::<Literal>(&nan).unwrap();
+        assert!(back.special_number().unwrap().as_f64().is_nan());
+
+        // Finite numbers are ordinary number literals.
+        assert_eq!(Literal::from_f64(1.5), Literal::new(serde_json::json!(1.5)));
+        assert_eq!(Literal::from_f64(1.5).special_number(), None);
+    }
+
+    #[test]
+    fn test_literal_serde_special_number_noncanonical_raw() {
+        let literal = serde_json::from_value::<Literal>(serde_json::json!({
+            "type": "Literal",
+            "start": 0,
+            "end": 3,
+            "kind": "specialNumber",
+            "value": "Infinity",
+            "raw": "inf",
+        }))
+        .unwrap();
+        assert_eq!(literal.special_number(), Some(SpecialNumber::Infinity));
+        assert_eq!(literal.raw, "Infinity");
+
+        // Memory can't hold it, so evaluating it is an error rather than null.
+        assert!(literal.get_result().is_err());
+        assert!(Literal::from_f64(1.5).get_result().is_ok());
+    }
+
+    #[test]
+    fn test_literal_schema_kind() {
+        // Every property name in a schema, and every value an `enum` in it allows.
+        fn collect(schema: &serde_json::Value, properties: &mut Vec<String>, values: &mut Vec<serde_json::Value>) {
+            match schema {
+                serde_json::Value::Object(map) => {
+                    if let Some(serde_json::Value::Object(props)) = map.get("properties") {
+                        properties.extend(props.keys().cloned());
+                    }
+                    if let Some(serde_json::Value::Array(allowed)) = map.get("enum") {
+                        values.extend(allowed.iter().cloned());
+                    }
+                    map.values().for_each(|v| collect(v, properties, values));
+                }
+                serde_json::Value::Array(items) => items.iter().for_each(|v| collect(v, properties, values)),
+                _ => {}
+            }
+        }
+
+        let schema = serde_json::to_value(schemars::schema_for!(Literal)).unwrap();
+        let (mut properties, mut kinds) = (Vec::new(), Vec::new());
+        collect(&schema, &mut properties, &mut Vec::new());
+        collect(&schema["definitions"]["LiteralKind"], &mut Vec::new(), &mut kinds);
+        assert!(!schema["required"].as_array().unwrap().contains(&"kind".into()));
+
+        for literal in [
+            Literal::new(serde_json::json!("5")),
+            Literal::new(serde_json::json!(5)),
+            Literal::new(serde_json::json!(true)),
+            Literal::from_f64(f64::NEG_INFINITY),
+        ] {
+            let json = serde_json::to_value(&literal).unwrap();
+            for key in json.as_object().unwrap().keys() {
+                assert!(properties.contains(key), "{key} isn't in the schema");
+            }
+            assert!(
+                kinds.contains(&json["kind"]),
+                "{} isn't a kind in the schema",
+                json["kind"]
+            );
+        }
+
+        // The frontend builds literals without a kind, and with any JSON value.
+        let decl = <Literal as ts_rs::TS>::decl();
+        assert!(decl.contains("kind?: LiteralKind"), "{decl}");
+        assert!(
+            decl.contains(&format!("value: {}", <serde_json::Value as ts_rs::TS>::name())),
+            "{decl}"
+        );
+    }
+
+    #[test]
+    fn test_non_code_meta_serde() {
+        let code = r#"// at the top
+const part = startSketchOn('XY')
+  // in a pipe
+  |> startProfileAt([0, 0], %)
+fn f = (a) => {
+  const b = a
+  // in a function body
+  return b
+}
+"#;
+        let program = crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+        let json = serde_json::to_string(&program).unwrap();
+        let back: Program = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, program);
+
+        // Every comment came back through the same `NonCodeMeta` deserializer, wherever it was.
+        let has_comment = |meta: &NonCodeMeta, text: &str| {
+            meta.start
+                .iter()
+                .chain(meta.non_code_nodes.values().flatten())
+                .any(|node| node.value().contains(text))
+        };
+        assert!(has_comment(&back.non_code_meta, "at the top"));
+        let BodyItem::VariableDeclaration(part) = &back.body[0] else {
+            panic!("expected a declaration");
+        };
+        let Value::PipeExpression(pipe) = &part.declarations[0].init else {
+            panic!("expected a pipe");
+        };
+        assert!(has_comment(&pipe.non_code_meta, "in a pipe"));
+        let BodyItem::VariableDeclaration(f) = &back.body[1] else {
+            panic!("expected a declaration");
+        };
+        let Value::FunctionExpression(function) = &f.declarations[0].init else {
+            panic!("expected a function");
+        };
+        assert!(has_comment(&function.body.non_code_meta, "in a function body"));
+
+        let json = serde_json::to_value(&function.body.non_code_meta).unwrap();
+        let keys: Vec<&String> = json["nonCodeNodes"].as_object().unwrap().keys().collect();
+        assert!(!keys.is_empty());
+        assert!(keys.iter().all(|key| key.parse::<usize>().is_ok()));
+    }
+
+    #[test]
+    fn test_pipe_stage_access() {
+        let code = "const part001 = startSketchOn('XY')\n  |> startProfileAt([0, 0], %)\n  |> line([0, 10], %)";
+        let tokens = crate::token::lexer(code);
+        let mut program = crate::parser::Parser::new(tokens).ast().unwrap();
+        let BodyItem::VariableDeclaration(declaration) = &mut program.body[0] else {
+            panic!("expected a variable declaration");
+        };
+        let Value::PipeExpression(pipe) = &mut declaration.declarations[0].init else {
+            panic!("expected a pipe expression");
+        };
+
+        let callee = |stage: Option<&Value>| match stage {
+            Some(Value::CallExpression(call)) => call.callee.name.clone(),
+            other => panic!("expected a call, found {:?}", other),
+        };
+        assert_eq!(callee(pipe.first_stage()), "startSketchOn");
+        assert_eq!(callee(pipe.stage(1)), "startProfileAt");
+        assert_eq!(callee(pipe.last_stage()), "line");
+        assert!(pipe.stage(3).is_none());
+        assert!(pipe.stage_mut(3).is_none());
+
+        *pipe.stage_mut(2).unwrap() = Value::Identifier(Box::new(Identifier::new("done")));
+        assert!(matches!(pipe.last_stage(), Some(Value::Identifier(_))));
+
+        assert!(PipeExpression::new(vec![]).last_stage().is_none());
+    }
+
+    #[test]
+    fn test_recast_normalize_comments() {
+        let code = "//nospace   \nconst x = 1 //   inline  \nconst y = 2";
+        let tokens = crate::token::lexer(code);
+        let mut program = crate::parser::Parser::new(tokens).ast().unwrap();
+        let options = FormatOptions {
+            normalize_comments: true,
+            ..Default::default()
+        };
+        let expected = "// nospace\nconst x = 1 // inline\nconst y = 2\n";
+        assert_eq!(program.recast(&options, 0), expected);
+
+        // Comments from somewhere other than the parser might not be trimmed yet.
+        program.non_code_meta.start[0].value = NonCodeValue::BlockComment {
+            value: "nospace   ".to_string(),
+            style: CommentStyle::Line,
+        };
+        assert_eq!(program.recast(&options, 0), expected);
+        assert!(program
+            .recast(&Default::default(), 0)
+            .starts_with("// nospace   \nconst x = 1"));
+    }
+
+    #[test]
+    fn test_call_substitution_positions() {
+        let code = "const part001 = startSketchOn('XY')\n  |> foo(a, %, b)\n  |> bar([%, 1], 2, baz(%))\n  |> qux(1)";
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+        let BodyItem::VariableDeclaration(declaration) = &program.body[0] else {
+            panic!("expected a variable declaration");
+        };
+        let Value::PipeExpression(pipe) = &declaration.declarations[0].init else {
+            panic!("expected a pipe expression");
+        };
+
+        let positions = |index: usize| match pipe.stage(index) {
+            Some(Value::CallExpression(call)) => call.substitution_positions(),
+            other => panic!("expected a call, found {:?}", other),
+        };
+        assert_eq!(positions(1), vec![1]);
+        assert_eq!(positions(2), vec![0, 2]);
+        assert!(positions(3).is_empty());
+    }
+
+    #[test]
+    fn test_statement_and_expression_count() {
+        let code = r#"const x = 1 + 2
+fn f = (a) => {
+  const y = a * 2
+  return y
+}
+show(f(x))"#;
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+
+        assert_eq!(program.statement_count(false), 3);
+        assert_eq!(program.statement_count(true), 5);
+        // `1 + 2`, `1`, `2`; the function, `a * 2`, `a`, `2`, `y`; `show(..)`, `f(x)`, `x`.
+        assert_eq!(program.expression_count(), 11);
+    }
+
+    #[test]
+    fn test_recast_preserve_collection_breaks() {
+        let code = r#"const square = [
+  [0, 0], [1, 0],
+  [1, 1], [0, 1]
+]
+const size = { width: 1,
+  height: 2 }"#;
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+        let options = FormatOptions {
+            preserve_collection_breaks: true,
+            ..Default::default()
+        };
+        assert_eq!(program.recast(&options, 0), format!("{}\n", code));
+
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            r#"const square = [[0, 0], [1, 0], [1, 1], [0, 1]]
+const size = { width: 1, height: 2 }
+"#
+        );
+    }
+
+    #[test]
+    fn test_recast_preserve_nested_collection_breaks() {
+        let code = r#"const grid = [
+  [
+    1,
+    2
+  ],
+  [3, 4]
+]
+const box = {
+  size: [
+    1,
+    2
+  ],
+  depth: 3
+}"#;
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+        let options = FormatOptions {
+            preserve_collection_breaks: true,
+            ..Default::default()
+        };
+        let recast = program.recast(&options, 0);
+        assert_eq!(recast, format!("{}\n", code));
+
+        let tokens = crate::token::lexer(&recast);
+        let reparsed = crate::parser::Parser::new(tokens).ast().unwrap();
+        assert_eq!(reparsed.recast(&options, 0), recast);
+    }
+
+    #[test]
+    fn test_recast_explicit_substitution() {
+        let code =
+            "const x = startSketchOn('XY')\n  |> startProfileAt([0, 0], %)\n  |> g(%)\n  |> close(%)\nshow(f(%))";
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+
+        assert_eq!(program.recast(&Default::default(), 0), format!("{}\n", code));
+        let options = FormatOptions {
+            explicit_substitution: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            program.recast(&options, 0),
+            "const x = startSketchOn('XY')\n  |> startProfileAt([0, 0], %)\n  |> g()\n  |> close()\nshow(f(%))\n"
+        );
+    }
+
+    #[test]
+    fn test_format_presets() {
+        let code = r#"const x = (a * b) + c
+const size = { width: 1,
+  height: 2 }
+fn area = (w, h) => {
+  return w * h
+}"#;
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+
+        assert_eq!(
+            program.recast(&FormatOptions::default_pretty(), 0),
+            format!("{}\n", code)
+        );
+        assert_eq!(
+            program.recast(&FormatOptions::compact(), 0),
+            "const x = a * b + c\nconst size = { width: 1, height: 2 }\nfn area = (w, h) => {\n\treturn w * h\n}"
+        );
+        assert_eq!(
+            program.recast(&FormatOptions::canonical(), 0),
+            "const x = a * b + c\nconst size = { width: 1, height: 2 }\nfn area = (w, h) => {\n  return w * h\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_recast_quote_style() {
+        let code = r#"const a = "XY"
+const b = 'XZ'
+const c = "it's"
+const d = 'say "hi"'
+const e = 'it\'s "hi"'"#;
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+
+        assert_eq!(program.recast(&Default::default(), 0), format!("{}\n", code));
+        // Strings that can't be written in the chosen quotes keep to the other ones, however
+        // they were written.
+        assert_eq!(
+            program.recast(&FormatOptions::canonical(), 0),
+            r#"const a = 'XY'
+const b = 'XZ'
+const c = "it's"
+const d = 'say "hi"'
+const e = 'it\'s "hi"'
+"#
+        );
+        let options = FormatOptions {
+            quote_style: QuoteStyle::Double,
+            ..Default::default()
+        };
+        assert_eq!(
+            program.recast(&options, 0),
+            r#"const a = "XY"
+const b = "XZ"
+const c = "it's"
+const d = 'say "hi"'
+const e = 'it\'s "hi"'
+"#
+        );
+    }
+
+    #[test]
+    fn test_recast_call_arguments() {
+        let code = "foo()\nfoo(a)\nfoo(a, b,)";
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+
+        // No stray space or comma, and the trailing comma isn't written back.
+        for options in [
+            FormatOptions::default_pretty(),
+            FormatOptions::compact(),
+            FormatOptions::canonical(),
+        ] {
+            assert_eq!(program.recast(&options, 0).trim_end(), "foo()\nfoo(a)\nfoo(a, b)");
+        }
+
+        assert!(crate::parser::Parser::new(crate::token::lexer("foo(,)")).ast().is_err());
+    }
+
+    /// Recasting a program's recast must give back exactly the same code.
+    fn assert_idempotent(src: &str) {
+        let recast = |code: &str| {
+            let tokens = crate::token::lexer(code);
+            let program = crate::parser::Parser::new(tokens).ast().unwrap();
+            program.recast(&Default::default(), 0)
+        };
+        let once = recast(src);
+        let twice = recast(&once);
+        assert_eq!(twice, once, "recasting the recast of\n{}\nchanged it", src);
+    }
+
+    #[test]
+    fn test_recast_is_idempotent() {
+        // Block comments (`/* */`) used to be written without a line break after them, so
+        // the next statement ran on from the comment and the recast didn't parse.
+        let corpus = [
+            include_str!("../../../tests/executor/inputs/cube.kcl"),
+            include_str!("../../../tests/executor/inputs/kittycad_svg.kcl"),
+            include_str!("../../../tests/executor/inputs/pipes_on_pipes.kcl"),
+            "/* comment at start */\nconst x = 1\nshow(x)",
+            "const x = 1 /* inline block */\nconst y = 2",
+            "const x = 1\n/* on its own line */\nconst y = 2",
+            "fn f = (a) => {\n  const b = a /* inline */\n  /* own line */\n  return b\n}",
+            "const x = 1 // inline\n\n// after a blank line\nconst y = 2",
+            "const part = startSketchOn('XY')\n  |> startProfileAt([0, 0], %) // start\n  // between\n  |> line([1, 1], %)",
+            "const a = (1 + 2) * 3 - 4 / (5 - 6)\nconst b = -a + 2 * 3",
+            "const obj = { width: 10, height: 20, depth: 30, name: 'a long name' }\nconst arr = [obj.width, obj['height'], arr2[0]]",
+        ];
+        for src in corpus {
+            assert_idempotent(src);
+        }
+    }
+
+    #[test]
+    fn test_recast_block_comment_ends_its_line() {
+        let code = "/* first */\nconst x = 1 /* inline */\n/* own line */\nconst y = 2";
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+        assert_eq!(program.recast(&Default::default(), 0), format!("{}\n", code));
+    }
 }
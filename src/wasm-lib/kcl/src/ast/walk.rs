@@ -0,0 +1,716 @@
+//! Traversal of the AST: read-only through [`NodeRef`], and in place through [`VisitMut`].
+
+use std::collections::VecDeque;
+
+use crate::{
+    ast::types::{
+        ArrayExpression, BinaryExpression, BinaryPart, BodyItem, CallExpression, ExpressionStatement,
+        FunctionExpression, Identifier, Literal, LiteralIdentifier, MemberExpression, MemberObject, ObjectExpression,
+        ObjectProperty, PipeExpression, PipeSubstitution, Program, ReturnStatement, UnaryExpression, Value,
+        VariableDeclaration, VariableDeclarator,
+    },
+    executor::SourceRange,
+};
+
+/// A borrowed reference to any node in the AST.
+#[derive(Debug, Clone, Copy)]
+pub enum NodeRef<'a> {
+    Program(&'a Program),
+    ExpressionStatement(&'a ExpressionStatement),
+    VariableDeclaration(&'a VariableDeclaration),
+    VariableDeclarator(&'a VariableDeclarator),
+    ReturnStatement(&'a ReturnStatement),
+    Literal(&'a Literal),
+    Identifier(&'a Identifier),
+    BinaryExpression(&'a BinaryExpression),
+    FunctionExpression(&'a FunctionExpression),
+    CallExpression(&'a CallExpression),
+    PipeExpression(&'a PipeExpression),
+    PipeSubstitution(&'a PipeSubstitution),
+    ArrayExpression(&'a ArrayExpression),
+    ObjectExpression(&'a ObjectExpression),
+    ObjectProperty(&'a ObjectProperty),
+    MemberExpression(&'a MemberExpression),
+    UnaryExpression(&'a UnaryExpression),
+}
+
+impl<'a> NodeRef<'a> {
+    pub fn start(&self) -> usize {
+        match self {
+            NodeRef::Program(n) => n.start,
+            NodeRef::ExpressionStatement(n) => n.start,
+            NodeRef::VariableDeclaration(n) => n.start,
+            NodeRef::VariableDeclarator(n) => n.start,
+            NodeRef::ReturnStatement(n) => n.start,
+            NodeRef::Literal(n) => n.start,
+            NodeRef::Identifier(n) => n.start,
+            NodeRef::BinaryExpression(n) => n.start,
+            NodeRef::FunctionExpression(n) => n.start,
+            NodeRef::CallExpression(n) => n.start,
+            NodeRef::PipeExpression(n) => n.start,
+            NodeRef::PipeSubstitution(n) => n.start,
+            NodeRef::ArrayExpression(n) => n.start,
+            NodeRef::ObjectExpression(n) => n.start,
+            NodeRef::ObjectProperty(n) => n.start,
+            NodeRef::MemberExpression(n) => n.start,
+            NodeRef::UnaryExpression(n) => n.start,
+        }
+    }
+
+    pub fn end(&self) -> usize {
+        match self {
+            NodeRef::Program(n) => n.end,
+            NodeRef::ExpressionStatement(n) => n.end,
+            NodeRef::VariableDeclaration(n) => n.end,
+            NodeRef::VariableDeclarator(n) => n.end,
+            NodeRef::ReturnStatement(n) => n.end,
+            NodeRef::Literal(n) => n.end,
+            NodeRef::Identifier(n) => n.end,
+            NodeRef::BinaryExpression(n) => n.end,
+            NodeRef::FunctionExpression(n) => n.end,
+            NodeRef::CallExpression(n) => n.end,
+            NodeRef::PipeExpression(n) => n.end,
+            NodeRef::PipeSubstitution(n) => n.end,
+            NodeRef::ArrayExpression(n) => n.end,
+            NodeRef::ObjectExpression(n) => n.end,
+            NodeRef::ObjectProperty(n) => n.end,
+            NodeRef::MemberExpression(n) => n.end,
+            NodeRef::UnaryExpression(n) => n.end,
+        }
+    }
+
+    /// Call `f` on each direct child of this node, in source order.
+    pub fn for_each_child(&self, mut f: impl FnMut(NodeRef<'a>)) {
+        match *self {
+            NodeRef::Program(program) => program.body.iter().for_each(|item| f(item.into())),
+            NodeRef::ExpressionStatement(statement) => f((&statement.expression).into()),
+            NodeRef::VariableDeclaration(declaration) => declaration
+                .declarations
+                .iter()
+                .for_each(|declarator| f(NodeRef::VariableDeclarator(declarator))),
+            NodeRef::VariableDeclarator(declarator) => {
+                f(NodeRef::Identifier(&declarator.id));
+                f((&declarator.init).into());
+            }
+            NodeRef::ReturnStatement(statement) => f((&statement.argument).into()),
+            NodeRef::Literal(_) | NodeRef::Identifier(_) | NodeRef::PipeSubstitution(_) => {}
+            NodeRef::BinaryExpression(binary) => {
+                f((&binary.left).into());
+                f((&binary.right).into());
+            }
+            NodeRef::FunctionExpression(function) => {
+                function.params.iter().for_each(|param| f(NodeRef::Identifier(param)));
+                f(NodeRef::Program(&function.body));
+            }
+            NodeRef::CallExpression(call) => {
+                f(NodeRef::Identifier(&call.callee));
+                call.arguments.iter().for_each(|arg| f(arg.into()));
+            }
+            NodeRef::PipeExpression(pipe) => pipe.body.iter().for_each(|value| f(value.into())),
+            NodeRef::ArrayExpression(array) => array.elements.iter().for_each(|value| f(value.into())),
+            NodeRef::ObjectExpression(object) => object
+                .properties
+                .iter()
+                .for_each(|property| f(NodeRef::ObjectProperty(property))),
+            NodeRef::ObjectProperty(property) => {
+                f(NodeRef::Identifier(&property.key));
+                f((&property.value).into());
+            }
+            NodeRef::MemberExpression(member) => {
+                f((&member.object).into());
+                f((&member.property).into());
+            }
+            NodeRef::UnaryExpression(unary) => f((&unary.argument).into()),
+        }
+    }
+
+    /// The direct children of this node, in source order.
+    pub fn children(&self) -> Vec<NodeRef<'a>> {
+        let mut children = Vec::new();
+        self.for_each_child(|child| children.push(child));
+        children
+    }
+
+    /// Visit this node and all of its descendants depth-first, parents before children.
+    pub fn walk(self, f: &mut impl FnMut(NodeRef<'a>)) {
+        f(self);
+        self.for_each_child(|child| child.walk(f));
+    }
+}
+
+impl<'a> NodeRef<'a> {
+    /// Whether two nodes hold the same tree, ignoring source positions and comments.
+    pub fn ast_eq(self, other: NodeRef<'_>) -> bool {
+        if !self.shallow_eq(other) {
+            return false;
+        }
+        let (ours, theirs) = (self.children(), other.children());
+        ours.len() == theirs.len() && ours.into_iter().zip(theirs).all(|(a, b)| a.ast_eq(b))
+    }
+
+    /// Whether two nodes are the same kind of node with the same data, not looking at
+    /// their children or source positions.
+    pub fn shallow_eq(self, other: NodeRef<'_>) -> bool {
+        match (self, other) {
+            (NodeRef::VariableDeclaration(a), NodeRef::VariableDeclaration(b)) => a.kind == b.kind,
+            (NodeRef::Literal(a), NodeRef::Literal(b)) => a.value == b.value,
+            (NodeRef::Identifier(a), NodeRef::Identifier(b)) => a.name == b.name,
+            (NodeRef::BinaryExpression(a), NodeRef::BinaryExpression(b)) => a.operator == b.operator,
+            (NodeRef::CallExpression(a), NodeRef::CallExpression(b)) => a.optional == b.optional,
+            (NodeRef::MemberExpression(a), NodeRef::MemberExpression(b)) => a.computed == b.computed,
+            (NodeRef::UnaryExpression(a), NodeRef::UnaryExpression(b)) => a.operator == b.operator,
+            (NodeRef::Program(_), NodeRef::Program(_))
+            | (NodeRef::ExpressionStatement(_), NodeRef::ExpressionStatement(_))
+            | (NodeRef::VariableDeclarator(_), NodeRef::VariableDeclarator(_))
+            | (NodeRef::ReturnStatement(_), NodeRef::ReturnStatement(_))
+            | (NodeRef::FunctionExpression(_), NodeRef::FunctionExpression(_))
+            | (NodeRef::PipeExpression(_), NodeRef::PipeExpression(_))
+            | (NodeRef::PipeSubstitution(_), NodeRef::PipeSubstitution(_))
+            | (NodeRef::ArrayExpression(_), NodeRef::ArrayExpression(_))
+            | (NodeRef::ObjectExpression(_), NodeRef::ObjectExpression(_))
+            | (NodeRef::ObjectProperty(_), NodeRef::ObjectProperty(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Whether two programs are the same, ignoring source positions and comments.
+pub fn ast_eq(a: &Program, b: &Program) -> bool {
+    NodeRef::Program(a).ast_eq(NodeRef::Program(b))
+}
+
+/// A difference between two programs, found by [`diff`].
+#[derive(Debug, Clone, Copy)]
+pub enum Change<'a> {
+    /// A node became a different kind of node, or the same kind with different data
+    /// (like another literal value or operator).
+    Replaced { old: NodeRef<'a>, new: NodeRef<'a> },
+    /// A node only in the new program, past the end of the old list it would be in.
+    Added(NodeRef<'a>),
+    /// A node only in the old program, past the end of the new list it would be in.
+    Removed(NodeRef<'a>),
+}
+
+/// The differences between two programs, ignoring source positions and comments.
+/// Lists (statements, arguments, elements and so on) are compared position by position, so
+/// inserting a statement shows up as changes to every statement after it.
+pub fn diff<'a>(old: &'a Program, new: &'a Program) -> Vec<Change<'a>> {
+    fn visit<'a>(old: NodeRef<'a>, new: NodeRef<'a>, changes: &mut Vec<Change<'a>>) {
+        if !old.shallow_eq(new) {
+            changes.push(Change::Replaced { old, new });
+            return;
+        }
+        let (ours, theirs) = (old.children(), new.children());
+        for (a, b) in ours.iter().zip(&theirs) {
+            visit(*a, *b, changes);
+        }
+        changes.extend(ours.iter().skip(theirs.len()).map(|node| Change::Removed(*node)));
+        changes.extend(theirs.iter().skip(ours.len()).map(|node| Change::Added(*node)));
+    }
+
+    let mut changes = Vec::new();
+    visit(NodeRef::Program(old), NodeRef::Program(new), &mut changes);
+    changes
+}
+
+/// Visit every node in the program depth-first, in source order.
+pub fn walk<'a>(program: &'a Program, mut f: impl FnMut(NodeRef<'a>)) {
+    NodeRef::Program(program).walk(&mut f);
+}
+
+/// Combine every node in the program into one value, visiting them depth-first in source order
+/// like [`walk`]. `f` gets the value so far and the next node, and returns the new value.
+pub fn fold<'a, T>(program: &'a Program, init: T, f: impl Fn(T, NodeRef<'a>) -> T) -> T {
+    fn fold_node<'a, T>(node: NodeRef<'a>, acc: T, f: &impl Fn(T, NodeRef<'a>) -> T) -> T {
+        node.children()
+            .into_iter()
+            .fold(f(acc, node), |acc, child| fold_node(child, acc, f))
+    }
+
+    fold_node(NodeRef::Program(program), init, &f)
+}
+
+/// Every node in the program, depth-first in source order like [`walk`], produced lazily.
+pub fn nodes(program: &Program) -> Nodes<'_> {
+    Nodes {
+        stack: vec![NodeRef::Program(program)],
+    }
+}
+
+/// The iterator returned by [`nodes`].
+#[derive(Debug, Clone)]
+pub struct Nodes<'a> {
+    /// The nodes still to visit, the next one last.
+    stack: Vec<NodeRef<'a>>,
+}
+
+impl<'a> Iterator for Nodes<'a> {
+    type Item = NodeRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        let first_child = self.stack.len();
+        node.for_each_child(|child| self.stack.push(child));
+        self.stack[first_child..].reverse();
+        Some(node)
+    }
+}
+
+/// Every literal in the program, in source order.
+pub fn iter_literals(program: &Program) -> impl Iterator<Item = &Literal> {
+    nodes(program).filter_map(|node| match node {
+        NodeRef::Literal(literal) => Some(literal),
+        _ => None,
+    })
+}
+
+/// Every call in the program, in source order. A call comes before the calls in its arguments.
+pub fn iter_calls(program: &Program) -> impl Iterator<Item = &CallExpression> {
+    nodes(program).filter_map(|node| match node {
+        NodeRef::CallExpression(call) => Some(call),
+        _ => None,
+    })
+}
+
+/// Every identifier in the program, in source order: names being declared, parameters, callees,
+/// object keys and member names as well as names being read.
+pub fn iter_identifiers(program: &Program) -> impl Iterator<Item = &Identifier> {
+    nodes(program).filter_map(|node| match node {
+        NodeRef::Identifier(identifier) => Some(identifier),
+        _ => None,
+    })
+}
+
+/// The source ranges of every node `predicate` picks, in source order, for highlighting a
+/// category of node in one pass.
+pub fn spans_of(program: &Program, predicate: impl Fn(NodeRef<'_>) -> bool) -> Vec<SourceRange> {
+    nodes(program)
+        .filter(|node| predicate(*node))
+        .map(|node| SourceRange([node.start(), node.end()]))
+        .collect()
+}
+
+/// Visit every node in the program breadth-first: the program, then its statements, then their
+/// children and so on, each level in source order. `f` also gets the depth of the node, which
+/// is 0 for the program itself.
+pub fn walk_bfs<'a>(program: &'a Program, mut f: impl FnMut(NodeRef<'a>, usize)) {
+    let mut queue = VecDeque::from([(NodeRef::Program(program), 0)]);
+    while let Some((node, depth)) = queue.pop_front() {
+        f(node, depth);
+        node.for_each_child(|child| queue.push_back((child, depth + 1)));
+    }
+}
+
+/// A traversal that can change the expressions in a program as it goes. Every hook's default
+/// visits the node's children with the matching `walk_*_mut` function, so an implementation
+/// overrides the hooks for the nodes it cares about, and calls the walk function from its hook
+/// to keep going into the node (or doesn't, to skip it).
+pub trait VisitMut {
+    fn visit_program(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+
+    fn visit_body_item(&mut self, item: &mut BodyItem) {
+        walk_body_item_mut(self, item);
+    }
+
+    fn visit_value(&mut self, value: &mut Value) {
+        walk_value_mut(self, value);
+    }
+
+    fn visit_part(&mut self, part: &mut BinaryPart) {
+        walk_part_mut(self, part);
+    }
+
+    fn visit_binary_expression(&mut self, binary: &mut BinaryExpression) {
+        walk_binary_expression_mut(self, binary);
+    }
+
+    fn visit_unary_expression(&mut self, unary: &mut UnaryExpression) {
+        walk_unary_expression_mut(self, unary);
+    }
+
+    fn visit_function_expression(&mut self, function: &mut FunctionExpression) {
+        walk_function_expression_mut(self, function);
+    }
+
+    fn visit_call_expression(&mut self, call: &mut CallExpression) {
+        walk_call_expression_mut(self, call);
+    }
+
+    fn visit_pipe_expression(&mut self, pipe: &mut PipeExpression) {
+        walk_pipe_expression_mut(self, pipe);
+    }
+
+    fn visit_array_expression(&mut self, array: &mut ArrayExpression) {
+        walk_array_expression_mut(self, array);
+    }
+
+    fn visit_object_expression(&mut self, object: &mut ObjectExpression) {
+        walk_object_expression_mut(self, object);
+    }
+
+    fn visit_member_expression(&mut self, member: &mut MemberExpression) {
+        walk_member_expression_mut(self, member);
+    }
+
+    /// The property in a member expression's brackets: the `i` in `xs[i]`, or the `0` in `xs[0]`.
+    /// A property after a `.` is a name rather than an expression, so isn't visited.
+    fn visit_member_property(&mut self, _property: &mut LiteralIdentifier) {}
+}
+
+pub fn walk_program_mut<V: VisitMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    for item in &mut program.body {
+        visitor.visit_body_item(item);
+    }
+}
+
+pub fn walk_body_item_mut<V: VisitMut + ?Sized>(visitor: &mut V, item: &mut BodyItem) {
+    match item {
+        BodyItem::ExpressionStatement(statement) => visitor.visit_value(&mut statement.expression),
+        BodyItem::VariableDeclaration(declaration) => {
+            for declarator in &mut declaration.declarations {
+                visitor.visit_value(&mut declarator.init);
+            }
+        }
+        BodyItem::ReturnStatement(statement) => visitor.visit_value(&mut statement.argument),
+    }
+}
+
+/// Visit the node a value holds. Literals, identifiers and `%` have no expressions inside them,
+/// so there's nothing to visit for them.
+pub fn walk_value_mut<V: VisitMut + ?Sized>(visitor: &mut V, value: &mut Value) {
+    match value {
+        Value::BinaryExpression(binary) => visitor.visit_binary_expression(binary),
+        Value::UnaryExpression(unary) => visitor.visit_unary_expression(unary),
+        Value::FunctionExpression(function) => visitor.visit_function_expression(function),
+        Value::CallExpression(call) => visitor.visit_call_expression(call),
+        Value::PipeExpression(pipe) => visitor.visit_pipe_expression(pipe),
+        Value::ArrayExpression(array) => visitor.visit_array_expression(array),
+        Value::ObjectExpression(object) => visitor.visit_object_expression(object),
+        Value::MemberExpression(member) => visitor.visit_member_expression(member),
+        Value::Literal(_) | Value::Identifier(_) | Value::PipeSubstitution(_) => {}
+    }
+}
+
+/// Visit the node an operand holds, like [`walk_value_mut`].
+pub fn walk_part_mut<V: VisitMut + ?Sized>(visitor: &mut V, part: &mut BinaryPart) {
+    match part {
+        BinaryPart::BinaryExpression(binary) => visitor.visit_binary_expression(binary),
+        BinaryPart::UnaryExpression(unary) => visitor.visit_unary_expression(unary),
+        BinaryPart::CallExpression(call) => visitor.visit_call_expression(call),
+        BinaryPart::MemberExpression(member) => visitor.visit_member_expression(member),
+        BinaryPart::Literal(_) | BinaryPart::Identifier(_) => {}
+    }
+}
+
+pub fn walk_binary_expression_mut<V: VisitMut + ?Sized>(visitor: &mut V, binary: &mut BinaryExpression) {
+    visitor.visit_part(&mut binary.left);
+    visitor.visit_part(&mut binary.right);
+}
+
+pub fn walk_unary_expression_mut<V: VisitMut + ?Sized>(visitor: &mut V, unary: &mut UnaryExpression) {
+    visitor.visit_part(&mut unary.argument);
+}
+
+pub fn walk_function_expression_mut<V: VisitMut + ?Sized>(visitor: &mut V, function: &mut FunctionExpression) {
+    visitor.visit_program(&mut function.body);
+}
+
+pub fn walk_call_expression_mut<V: VisitMut + ?Sized>(visitor: &mut V, call: &mut CallExpression) {
+    for arg in &mut call.arguments {
+        visitor.visit_value(arg);
+    }
+}
+
+pub fn walk_pipe_expression_mut<V: VisitMut + ?Sized>(visitor: &mut V, pipe: &mut PipeExpression) {
+    for stage in &mut pipe.body {
+        visitor.visit_value(stage);
+    }
+}
+
+pub fn walk_array_expression_mut<V: VisitMut + ?Sized>(visitor: &mut V, array: &mut ArrayExpression) {
+    for element in &mut array.elements {
+        visitor.visit_value(element);
+    }
+}
+
+pub fn walk_object_expression_mut<V: VisitMut + ?Sized>(visitor: &mut V, object: &mut ObjectExpression) {
+    for property in &mut object.properties {
+        visitor.visit_value(&mut property.value);
+    }
+}
+
+/// Visit the member expressions the object is made of, then the property if it's in brackets,
+/// so `xs[i][j]` visits `xs[i]`, then `i`, then `j`. The visitor may swap a bracketed name for a
+/// literal or the other way round, so `computed` is brought back in line with the property
+/// afterwards: the parser only sets it for a name in brackets.
+pub fn walk_member_expression_mut<V: VisitMut + ?Sized>(visitor: &mut V, member: &mut MemberExpression) {
+    if let MemberObject::MemberExpression(object) = &mut member.object {
+        visitor.visit_member_expression(object);
+    }
+    let bracketed = member.computed || matches!(member.property, LiteralIdentifier::Literal(_));
+    if bracketed {
+        visitor.visit_member_property(&mut member.property);
+        member.computed = matches!(member.property, LiteralIdentifier::Identifier(_));
+    }
+}
+
+/// Call `f` on every value in the program, the values inside it before the value itself, so `f`
+/// can replace a value without the replacement being visited.
+pub fn for_each_value_mut(program: &mut Program, f: impl FnMut(&mut Value)) {
+    struct Values<F>(F);
+
+    impl<F: FnMut(&mut Value)> VisitMut for Values<F> {
+        fn visit_value(&mut self, value: &mut Value) {
+            walk_value_mut(self, value);
+            (self.0)(value);
+        }
+    }
+
+    Values(f).visit_program(program);
+}
+
+impl<'a> From<&'a BodyItem> for NodeRef<'a> {
+    fn from(item: &'a BodyItem) -> Self {
+        match item {
+            BodyItem::ExpressionStatement(statement) => NodeRef::ExpressionStatement(statement),
+            BodyItem::VariableDeclaration(declaration) => NodeRef::VariableDeclaration(declaration),
+            BodyItem::ReturnStatement(statement) => NodeRef::ReturnStatement(statement),
+        }
+    }
+}
+
+impl<'a> From<&'a Value> for NodeRef<'a> {
+    fn from(value: &'a Value) -> Self {
+        match value {
+            Value::Literal(literal) => NodeRef::Literal(literal),
+            Value::Identifier(identifier) => NodeRef::Identifier(identifier),
+            Value::BinaryExpression(binary) => NodeRef::BinaryExpression(binary),
+            Value::FunctionExpression(function) => NodeRef::FunctionExpression(function),
+            Value::CallExpression(call) => NodeRef::CallExpression(call),
+            Value::PipeExpression(pipe) => NodeRef::PipeExpression(pipe),
+            Value::PipeSubstitution(substitution) => NodeRef::PipeSubstitution(substitution),
+            Value::ArrayExpression(array) => NodeRef::ArrayExpression(array),
+            Value::ObjectExpression(object) => NodeRef::ObjectExpression(object),
+            Value::MemberExpression(member) => NodeRef::MemberExpression(member),
+            Value::UnaryExpression(unary) => NodeRef::UnaryExpression(unary),
+        }
+    }
+}
+
+impl<'a> From<&'a BinaryPart> for NodeRef<'a> {
+    fn from(part: &'a BinaryPart) -> Self {
+        match part {
+            BinaryPart::Literal(literal) => NodeRef::Literal(literal),
+            BinaryPart::Identifier(identifier) => NodeRef::Identifier(identifier),
+            BinaryPart::BinaryExpression(binary) => NodeRef::BinaryExpression(binary),
+            BinaryPart::CallExpression(call) => NodeRef::CallExpression(call),
+            BinaryPart::UnaryExpression(unary) => NodeRef::UnaryExpression(unary),
+            BinaryPart::MemberExpression(member) => NodeRef::MemberExpression(member),
+        }
+    }
+}
+
+impl<'a> From<&'a MemberObject> for NodeRef<'a> {
+    fn from(object: &'a MemberObject) -> Self {
+        match object {
+            MemberObject::MemberExpression(member) => NodeRef::MemberExpression(member),
+            MemberObject::Identifier(identifier) => NodeRef::Identifier(identifier),
+        }
+    }
+}
+
+impl<'a> From<&'a LiteralIdentifier> for NodeRef<'a> {
+    fn from(property: &'a LiteralIdentifier) -> Self {
+        match property {
+            LiteralIdentifier::Identifier(identifier) => NodeRef::Identifier(identifier),
+            LiteralIdentifier::Literal(literal) => NodeRef::Literal(literal),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_walk_visits_nodes_in_source_order() {
+        let code = "const x = 1 + y\nshow(x)";
+        let tokens = crate::token::lexer(code);
+        let program = crate::parser::Parser::new(tokens).ast().unwrap();
+
+        let mut starts = Vec::new();
+        walk(&program, |node| starts.push(node.start()));
+        let mut sorted = starts.clone();
+        sorted.sort();
+        assert_eq!(starts, sorted);
+
+        let mut identifiers = Vec::new();
+        walk(&program, |node| {
+            if let NodeRef::Identifier(identifier) = node {
+                identifiers.push(identifier.name.as_str());
+            }
+        });
+        assert_eq!(identifiers, vec!["x", "y", "show", "x"]);
+    }
+
+    #[test]
+    fn test_ast_eq_ignores_positions_and_comments() {
+        let parse = |code: &str| crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+        let a = parse("const x = 1 + y");
+        let b = parse("// a comment\nconst  x =   1 + y");
+        let c = parse("const x = 1 - y");
+        assert!(ast_eq(&a, &b));
+        assert!(!ast_eq(&a, &c));
+    }
+
+    #[test]
+    fn test_fold() {
+        let code = "const x = 1 + 2.5\nshow([x, 4], f(-3))";
+        let program = crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+        let number = |node: NodeRef<'_>| match node {
+            NodeRef::Literal(literal) => literal.value.as_f64(),
+            _ => None,
+        };
+
+        let folded = fold(&program, 0.0, |sum, node| sum + number(node).unwrap_or_default());
+        let mut sum = 0.0;
+        walk(&program, |node| sum += number(node).unwrap_or_default());
+        assert_eq!(folded, sum);
+        assert_eq!(folded, 10.5);
+
+        let names = fold(&program, String::new(), |names, node| match node {
+            NodeRef::Identifier(identifier) => names + identifier.name.as_str(),
+            _ => names,
+        });
+        assert_eq!(names, "xshowxf");
+    }
+
+    #[test]
+    fn test_iterators() {
+        let code = "const x = 1 + 2.5\nshow([x, 4], f(-3, g()))";
+        let program = crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+
+        assert_eq!(iter_literals(&program).count(), 4);
+        let raws: Vec<&str> = iter_literals(&program).map(|literal| literal.raw.as_str()).collect();
+        assert_eq!(raws, vec!["1", "2.5", "4", "3"]);
+
+        let callees: Vec<&str> = iter_calls(&program).map(|call| call.callee.name.as_str()).collect();
+        assert_eq!(callees, vec!["show", "f", "g"]);
+
+        let names: Vec<&str> = iter_identifiers(&program)
+            .map(|identifier| identifier.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["x", "show", "x", "f", "g"]);
+
+        // The same order `walk` visits in.
+        let mut walked = Vec::new();
+        walk(&program, |node| walked.push(node.start()));
+        assert_eq!(nodes(&program).map(|node| node.start()).collect::<Vec<_>>(), walked);
+    }
+
+    #[test]
+    fn test_spans_of() {
+        let code = "const x = 1 + 2.5\nshow([x, 4], f(-3, g('a')))";
+        let program = crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+
+        // A call starts with its callee, so that's the identifier starting where a call does.
+        let call_starts: Vec<usize> = iter_calls(&program).map(|call| call.start).collect();
+        let callees = spans_of(
+            &program,
+            |node| matches!(node, NodeRef::Identifier(identifier) if call_starts.contains(&identifier.start)),
+        );
+        assert_eq!(
+            callees,
+            vec![SourceRange([18, 22]), SourceRange([31, 32]), SourceRange([37, 38])]
+        );
+
+        let numbers = spans_of(
+            &program,
+            |node| matches!(node, NodeRef::Literal(literal) if literal.value.is_number()),
+        );
+        assert_eq!(
+            numbers,
+            vec![
+                SourceRange([10, 11]),
+                SourceRange([14, 17]),
+                SourceRange([27, 28]),
+                SourceRange([34, 35]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_walk_bfs() {
+        let code = "const x = 1 + y\nshow(x)";
+        let program = crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+
+        let mut visited = Vec::new();
+        walk_bfs(&program, |node, depth| {
+            let label = match node {
+                NodeRef::Identifier(identifier) => identifier.name.to_string(),
+                NodeRef::Literal(literal) => literal.raw.clone(),
+                NodeRef::Program(_) => "program".to_owned(),
+                NodeRef::VariableDeclaration(_) => "declaration".to_owned(),
+                NodeRef::VariableDeclarator(_) => "declarator".to_owned(),
+                NodeRef::ExpressionStatement(_) => "statement".to_owned(),
+                NodeRef::BinaryExpression(_) => "binary".to_owned(),
+                NodeRef::CallExpression(_) => "call".to_owned(),
+                _ => "other".to_owned(),
+            };
+            visited.push((label, depth));
+        });
+
+        let expected = [
+            ("program", 0),
+            ("declaration", 1),
+            ("statement", 1),
+            ("declarator", 2),
+            ("call", 2),
+            ("x", 3),
+            ("binary", 3),
+            ("show", 3),
+            ("x", 3),
+            ("1", 4),
+            ("y", 4),
+        ];
+        let expected: Vec<_> = expected.iter().map(|(label, depth)| (label.to_string(), *depth)).collect();
+        assert_eq!(visited, expected);
+    }
+
+    #[test]
+    fn test_visit_mut() {
+        let code = "const x = f(1 + 2, [a, b])\nfn g = (y) => {\n  return -y\n}";
+        let mut program = crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+
+        // Values are visited inside out, operands included.
+        let mut visited = Vec::new();
+        for_each_value_mut(&mut program, |value| visited.push(NodeRef::from(&*value).start()));
+        assert_eq!(visited, vec![12, 20, 23, 19, 10, 52, 34]);
+
+        struct RenameIdentifiers;
+
+        impl VisitMut for RenameIdentifiers {
+            fn visit_value(&mut self, value: &mut Value) {
+                if let Value::Identifier(identifier) = value {
+                    identifier.name = identifier.name.to_ascii_uppercase().as_str().into();
+                }
+                walk_value_mut(self, value);
+            }
+
+            fn visit_part(&mut self, part: &mut BinaryPart) {
+                if let BinaryPart::Identifier(identifier) = part {
+                    identifier.name = identifier.name.to_ascii_uppercase().as_str().into();
+                }
+                walk_part_mut(self, part);
+            }
+        }
+
+        RenameIdentifiers.visit_program(&mut program);
+        assert_eq!(
+            program.recast(&Default::default(), 0),
+            "const x = f(1 + 2, [A, B])\nfn g = (y) => {\n  return -Y\n}\n"
+        );
+    }
+}
@@ -796,7 +796,7 @@ pub async fn execute(
                     let mut args: Vec<MemoryItem> = Vec::new();
                     for arg in &call_expr.arguments {
                         match arg {
-                            Value::Literal(literal) => args.push(literal.into()),
+                            Value::Literal(literal) => args.push(literal.get_result()?),
                             Value::Identifier(identifier) => {
                                 let memory_item = memory.get(&identifier.name, identifier.into())?;
                                 args.push(memory_item.clone());
@@ -855,7 +855,7 @@ pub async fn execute(
 
                     match &declaration.init {
                         Value::Literal(literal) => {
-                            memory.add(&var_name, literal.into(), source_range)?;
+                            memory.add(&var_name, literal.get_result()?, source_range)?;
                         }
                         Value::Identifier(identifier) => {
                             let value = memory.get(&identifier.name, identifier.into())?;
@@ -967,7 +967,7 @@ pub async fn execute(
                     memory.return_ = Some(ProgramReturn::Value(value));
                 }
                 Value::Literal(literal) => {
-                    memory.return_ = Some(ProgramReturn::Value(literal.into()));
+                    memory.return_ = Some(ProgramReturn::Value(literal.get_result()?));
                 }
                 Value::ArrayExpression(array_expr) => {
                     let result = array_expr.execute(memory, &mut pipe_info, ctx).await?;
@@ -1177,6 +1177,32 @@ show(firstExtrude)"#;
         parse_execute(ast).await.unwrap();
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_execute_pipe_stage_without_substitution() {
+        let ast = r#"fn double = (x) => {
+  return x * 2
+}
+fn five = () => {
+  return 5
+}
+
+const x = double(3)
+  |> double()
+const y = double(3)
+  |> five()"#;
+
+        let memory = parse_execute(ast).await.unwrap();
+        // `double()` gets the previous stage's result, `five()` has nowhere to put it.
+        assert_eq!(
+            serde_json::json!(12.0),
+            memory.root.get("x").unwrap().get_json_value().unwrap()
+        );
+        assert_eq!(
+            serde_json::json!(5),
+            memory.root.get("y").unwrap().get_json_value().unwrap()
+        );
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_execute_with_function_unary_in_pipe() {
         let ast = r#"const w = 20
@@ -1412,6 +1438,22 @@ show(thisBox)
         );
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_execute_negative_array_index() {
+        let ast = r#"const xs = [1, 2, 3]
+const last = xs[-1]
+const first = xs[-3]"#;
+        let memory = parse_execute(ast).await.unwrap();
+        let value = |name: &str| memory.root.get(name).unwrap().get_json_value().unwrap().as_f64();
+        assert_eq!(value("last"), Some(3.0));
+        assert_eq!(value("first"), Some(1.0));
+
+        let err = parse_execute("const xs = [1, 2, 3]\nconst x = xs[-4]")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("index -4 not found in array"), "{err}");
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_math_execute() {
         let ast = r#"const myVar = 1 + 2 * (3 - 4) / -5 + 6"#;
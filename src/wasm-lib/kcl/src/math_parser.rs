@@ -58,6 +58,13 @@ pub struct ExtendedBinaryExpression {
 
 crate::ast::types::impl_value_meta!(ExtendedBinaryExpression);
 
+impl ExtendedBinaryExpression {
+    /// Extended means the expression was closed in parens.
+    fn parenthesized(&self) -> Option<bool> {
+        self.start_extended.map(|_| true)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, ts_rs::TS)]
 #[ts(export)]
 #[serde(tag = "type")]
@@ -363,6 +370,7 @@ impl ReversePolishNotation {
                     end: bin_exp.end,
                     left: bin_exp.left.clone(),
                     right: bin_exp.right.clone(),
+                    parenthesized: bin_exp.parenthesized(),
                 }),
                 MathExpression::BinaryExpression(bin_exp) => Ok(BinaryExpression {
                     operator: bin_exp.operator.clone(),
@@ -370,6 +378,7 @@ impl ReversePolishNotation {
                     end: bin_exp.end,
                     left: bin_exp.left.clone(),
                     right: bin_exp.right.clone(),
+                    parenthesized: bin_exp.parenthesized,
                 }),
 
                 a => {
@@ -445,7 +454,7 @@ impl ReversePolishNotation {
                         end: current_token.end,
                         operator: crate::ast::types::UnaryOperator::Neg,
                         argument: BinaryPart::Identifier(Box::new(Identifier {
-                            name: current_token.value.trim_start_matches('-').to_string(),
+                            name: current_token.value.trim_start_matches('-').into(),
                             start: current_token.start + 1,
                             end: current_token.end,
                         })),
@@ -453,7 +462,7 @@ impl ReversePolishNotation {
                     new_stack.push(MathExpression::UnaryExpression(Box::new(expression)));
                 } else {
                     new_stack.push(MathExpression::Identifier(Box::new(Identifier {
-                        name: current_token.value.clone(),
+                        name: current_token.value.as_str().into(),
                         start: current_token.start,
                         end: current_token.end,
                     })));
@@ -570,6 +579,7 @@ impl ReversePolishNotation {
                     end: bin_exp.end,
                     left: bin_exp.left.clone(),
                     right: bin_exp.right.clone(),
+                    parenthesized: bin_exp.parenthesized(),
                 })),
                 bin_exp.start_extended.unwrap_or(bin_exp.start),
             ),
@@ -608,6 +618,7 @@ impl ReversePolishNotation {
                     end: bin_exp.end,
                     left: bin_exp.left.clone(),
                     right: bin_exp.right.clone(),
+                    parenthesized: bin_exp.parenthesized(),
                 })),
                 bin_exp.end_extended.unwrap_or(bin_exp.end),
             ),
@@ -652,6 +663,7 @@ impl ReversePolishNotation {
             end: if right.1 > right_end { right.1 } else { right_end },
             left: left.0,
             right: right.0,
+            parenthesized: None,
         };
         let mut new_stack = stack[0..stack.len() - 2].to_vec();
         new_stack.push(MathExpression::BinaryExpression(Box::new(tree)));
@@ -692,6 +704,7 @@ impl MathParser {
             start: min_start,
             end: max_end,
             operator: tree_with_maybe_bad_top_level_start_end.operator,
+            parenthesized: tree_with_maybe_bad_top_level_start_end.parenthesized,
         })
     }
 }
@@ -725,6 +738,7 @@ mod test {
                     start: 4,
                     end: 5,
                 })),
+                parenthesized: None,
             }
         );
     }
@@ -752,6 +766,7 @@ mod test {
                     start: 2,
                     end: 3,
                 })),
+                parenthesized: None,
             }
         );
     }
@@ -779,6 +794,7 @@ mod test {
                     start: 3,
                     end: 4,
                 })),
+                parenthesized: None,
             }
         );
     }
@@ -816,7 +832,9 @@ mod test {
                         start: 8,
                         end: 9,
                     })),
+                    parenthesized: None,
                 })),
+                parenthesized: None,
             }
         );
     }
@@ -854,7 +872,9 @@ mod test {
                         start: 10,
                         end: 11,
                     })),
+                    parenthesized: Some(true),
                 })),
+                parenthesized: None,
             }
         );
     }
@@ -896,7 +916,9 @@ mod test {
                             start: 10,
                             end: 11,
                         })),
+                        parenthesized: Some(true),
                     })),
+                    parenthesized: None,
                 })),
                 right: BinaryPart::Literal(Box::new(Literal {
                     value: serde_json::Value::Number(serde_json::Number::from(4)),
@@ -904,6 +926,7 @@ mod test {
                     start: 16,
                     end: 17,
                 })),
+                parenthesized: None,
             }
         )
     }
@@ -945,6 +968,7 @@ mod test {
                             start: 10,
                             end: 11,
                         })),
+                        parenthesized: Some(true),
                     })),
                     right: BinaryPart::Literal(Box::new(Literal {
                         value: serde_json::Value::Number(serde_json::Number::from(4)),
@@ -952,7 +976,9 @@ mod test {
                         start: 16,
                         end: 17,
                     })),
+                    parenthesized: None,
                 })),
+                parenthesized: None,
             }
         )
     }
@@ -997,6 +1023,7 @@ mod test {
                                 start: 11,
                                 end: 12,
                             })),
+                            parenthesized: Some(true),
                         })),
                         right: BinaryPart::Literal(Box::new(Literal {
                             value: serde_json::Value::Number(serde_json::Number::from(4)),
@@ -1004,6 +1031,7 @@ mod test {
                             start: 17,
                             end: 18,
                         })),
+                        parenthesized: None,
                     })),
                     right: BinaryPart::Literal(Box::new(Literal {
                         value: serde_json::Value::Number(serde_json::Number::from(5)),
@@ -1011,7 +1039,9 @@ mod test {
                         start: 21,
                         end: 22,
                     })),
+                    parenthesized: Some(true),
                 })),
+                parenthesized: None,
             }
         )
     }
@@ -1048,7 +1078,9 @@ mod test {
                         start: 12,
                         end: 13,
                     })),
+                    parenthesized: Some(true),
                 })),
+                parenthesized: None,
             }
         )
     }
@@ -1200,6 +1232,7 @@ mod test {
                     start: 7,
                     end: 8,
                 })),
+                parenthesized: None,
             }
         )
     }
@@ -1264,7 +1297,9 @@ mod test {
                     start: 8,
                     end: 9,
                 })),
+                parenthesized: None,
             })),
+            parenthesized: None,
         };
         let mut parser = ReversePolishNotation::new(&[], &[], &[]);
         let output = parser.build_tree(&input_tokens, vec![]).unwrap();
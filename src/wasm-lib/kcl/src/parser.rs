@@ -1,14 +1,18 @@
 use std::{collections::HashMap, str::FromStr};
 
 use crate::{
-    ast::types::{
-        ArrayExpression, BinaryExpression, BinaryPart, BodyItem, CallExpression, CommentStyle, ExpressionStatement,
-        FunctionExpression, Identifier, Literal, LiteralIdentifier, MemberExpression, MemberObject, NonCodeMeta,
-        NonCodeNode, NonCodeValue, ObjectExpression, ObjectKeyInfo, ObjectProperty, PipeExpression, PipeSubstitution,
-        Program, ReturnStatement, UnaryExpression, UnaryOperator, Value, VariableDeclaration, VariableDeclarator,
-        VariableKind,
+    ast::{
+        error::KclAstError,
+        types::{
+            ArrayExpression, BinaryExpression, BinaryPart, BodyItem, CallExpression, CommentStyle, ExpressionStatement,
+            FunctionExpression, Identifier, Literal, LiteralIdentifier, MemberExpression, MemberObject, NonCodeMeta,
+            NonCodeNode, NonCodeValue, ObjectExpression, ObjectKeyInfo, ObjectProperty, PipeExpression,
+            PipeSubstitution, Program, ReturnStatement, UnaryExpression, UnaryOperator, Value, VariableDeclaration,
+            VariableDeclarator, VariableKind,
+        },
     },
     errors::{KclError, KclErrorDetails},
+    executor::SourceRange,
     math_parser::MathParser,
     token::{Token, TokenType},
 };
@@ -18,6 +22,21 @@ mod parser_impl;
 pub const PIPE_SUBSTITUTION_OPERATOR: &str = "%";
 pub const PIPE_OPERATOR: &str = "|>";
 
+/// Parse source that arrived as bytes (e.g. from the frontend) without copying it into a
+/// `String` first. Bytes that aren't valid UTF-8 are a parse error at the first bad byte.
+pub fn parse_bytes(bytes: &[u8]) -> Result<Program, KclAstError> {
+    let src = std::str::from_utf8(bytes).map_err(|e| {
+        let start = e.valid_up_to();
+        // A sequence cut off by the end of the input runs to the end.
+        let end = start + e.error_len().unwrap_or(bytes.len() - start);
+        KclAstError::Parse {
+            source_range: SourceRange([start, end]),
+            message: format!("invalid UTF-8 at byte {}", start),
+        }
+    })?;
+    Ok(Parser::new(crate::token::lexer(src)).ast()?)
+}
+
 #[derive(Debug, PartialEq, Clone)]
 struct TokenReturn {
     token: Option<Token>,
@@ -213,7 +232,7 @@ impl Parser {
         Ok(Identifier {
             start: current_token.start,
             end: current_token.end,
-            name: current_token.value.clone(),
+            name: current_token.value.as_str().into(),
         })
     }
 
@@ -1053,6 +1072,7 @@ impl Parser {
                 start: opening_brace_token.start,
                 end: closing_brace_token.end,
                 elements: array_elements.elements,
+                line_breaks: None,
             },
             last_index: array_elements.last_index,
         })
@@ -1609,6 +1629,7 @@ impl Parser {
                 start: opening_brace_token.start,
                 end: self.get_token(object_properties.last_index)?.end,
                 properties: object_properties.properties,
+                line_breaks: None,
             },
             last_index: object_properties.last_index,
         })
@@ -1844,7 +1865,7 @@ mod tests {
             Identifier {
                 start: 0,
                 end: 1,
-                name: "a".to_string()
+                name: "a".into()
             },
             identifier
         );
@@ -1859,7 +1880,7 @@ mod tests {
             Identifier {
                 start: 6,
                 end: 11,
-                name: "myVar".to_string()
+                name: "myVar".into()
             },
             identifier
         );
@@ -1874,7 +1895,7 @@ mod tests {
             Identifier {
                 start: 6,
                 end: 11,
-                name: "myVar".to_string()
+                name: "myVar".into()
             },
             identifier
         );
@@ -1883,7 +1904,7 @@ mod tests {
             Identifier {
                 start: 22,
                 end: 28,
-                name: "newVar".to_string()
+                name: "newVar".into()
             },
             identifier
         );
@@ -1898,7 +1919,7 @@ mod tests {
             Identifier {
                 start: 0,
                 end: 3,
-                name: "log".to_string()
+                name: "log".into()
             },
             identifier
         );
@@ -1907,7 +1928,7 @@ mod tests {
             Identifier {
                 start: 16,
                 end: 27,
-                name: "aIdentifier".to_string()
+                name: "aIdentifier".into()
             },
             identifier
         );
@@ -2023,6 +2044,7 @@ const key = 'c'"#,
                 value: serde_json::Value::String("a".to_owned()),
                 raw: r#""a""#.to_owned(),
             })),
+            parenthesized: None,
         };
         let expected = vec![BodyItem::ExpressionStatement(ExpressionStatement {
             start: 0,
@@ -2700,6 +2722,7 @@ show(mySk1)"#;
                         value: serde_json::Value::Number(serde_json::Number::from(6)),
                         raw: "6".to_string(),
                     })),
+                    parenthesized: None,
                 })),
             })],
             non_code_meta: NonCodeMeta::default(),
@@ -2967,7 +2990,7 @@ e
                     id: Identifier {
                         start: 6,
                         end: 13,
-                        name: "myArray".to_string(),
+                        name: "myArray".into(),
                     },
                     init: Value::ArrayExpression(Box::new(ArrayExpression {
                         start: 16,
@@ -3040,6 +3063,7 @@ e
                                 raw: "10".to_string(),
                             })),
                         ],
+                        line_breaks: None,
                     })),
                 }],
                 kind: VariableKind::Const,
@@ -3243,4 +3267,28 @@ show(myBox)"#;
         let parser = Parser::new(tokens);
         parser.ast().unwrap();
     }
+
+    #[test]
+    fn test_parse_bytes() {
+        let program = parse_bytes("const größe = 5".as_bytes()).unwrap();
+        assert_eq!(program.body.len(), 1);
+
+        // 0xff can't appear anywhere in UTF-8.
+        let err = parse_bytes(b"const x = \xff5").unwrap_err();
+        assert_eq!(
+            err,
+            KclAstError::Parse {
+                source_range: SourceRange([10, 11]),
+                message: "invalid UTF-8 at byte 10".to_owned(),
+            }
+        );
+
+        // A multi-byte character cut off at the end.
+        let err = parse_bytes(b"const x = '\xc3").unwrap_err();
+        assert_eq!(err.source_range(), Some(SourceRange([11, 12])));
+
+        // Errors from the parser itself are the same kind of error.
+        let err = parse_bytes(b"const x = )").unwrap_err();
+        assert!(matches!(err, KclAstError::Parse { .. }));
+    }
 }
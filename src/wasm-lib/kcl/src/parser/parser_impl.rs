@@ -200,7 +200,7 @@ fn bool_value(i: TokenSlice) -> PResult<Identifier> {
     Ok(Identifier {
         start: token.start,
         end: token.end,
-        name: name.to_owned(),
+        name: name.into(),
     })
 }
 
@@ -271,6 +271,26 @@ fn unsigned_number_literal(i: TokenSlice) -> PResult<Literal> {
     })
 }
 
+/// Parse a number literal with a `-` right before it, e.g. the `-1` in `xs[-1]`.
+/// Elsewhere that's a unary expression, but an index has to be a literal.
+fn negative_number_literal(i: TokenSlice) -> PResult<Literal> {
+    let minus = any
+        .verify(|token: &Token| matches!(token.token_type, TokenType::Operator) && token.value == "-")
+        .context(expected("a '-' before a number"))
+        .parse_next(i)?;
+    let number = unsigned_number_literal.parse_next(i)?;
+    let value = match number.value.as_i64() {
+        Some(n) => JValue::from(-n),
+        None => JValue::from(-number.value.as_f64().unwrap_or_default()),
+    };
+    Ok(Literal {
+        start: minus.start,
+        end: number.end,
+        value,
+        raw: format!("-{}", number.raw),
+    })
+}
+
 /// Parse a KCL operator that takes a left- and right-hand side argument.
 fn binary_operator(i: TokenSlice) -> PResult<BinaryOperator> {
     any.try_map(|token: Token| {
@@ -385,16 +405,47 @@ fn equals(i: TokenSlice) -> PResult<Token> {
 
 /// Parse a KCL array of elements.
 fn array(i: TokenSlice) -> PResult<ArrayExpression> {
+    let tokens = *i;
     let start = open_bracket(i)?.start;
     ignore_whitespace(i);
-    let elements = alt((integer_range, separated0(value, comma_sep)))
-        .context(expected(
-            "array contents, either a numeric range (like 0..10) or a list of elements (like [1, 2, 3])",
-        ))
-        .parse_next(i)?;
+    let (elements, is_range) = alt((
+        integer_range.map(|elements| (elements, true)),
+        separated0(value, comma_sep).map(|elements| (elements, false)),
+    ))
+    .context(expected(
+        "array contents, either a numeric range (like 0..10) or a list of elements (like [1, 2, 3])",
+    ))
+    .parse_next(i)?;
     ignore_whitespace(i);
-    let end = close_bracket(i)?.end;
-    Ok(ArrayExpression { start, end, elements })
+    let close = close_bracket(i)?;
+    let line_breaks = if is_range {
+        None
+    } else {
+        line_breaks(tokens, elements.iter().map(Value::start).chain([close.start]))
+    };
+    Ok(ArrayExpression {
+        start,
+        end: close.end,
+        elements,
+        line_breaks,
+    })
+}
+
+/// The indices of the offsets which are the first thing on their line, or `None` if there are
+/// none.
+fn line_breaks(tokens: &[Token], offsets: impl Iterator<Item = usize>) -> Option<Vec<usize>> {
+    let line_breaks: Vec<usize> = offsets
+        .enumerate()
+        .filter(|(_, offset)| {
+            let index = tokens.partition_point(|token| token.start < *offset);
+            index > 0 && {
+                let previous = &tokens[index - 1];
+                previous.token_type == TokenType::Whitespace && previous.value.contains('\n')
+            }
+        })
+        .map(|(index, _)| index)
+        .collect();
+    Some(line_breaks).filter(|line_breaks| !line_breaks.is_empty())
 }
 
 /// Parse n..m into a vec of numbers [n, n+1, ..., m]
@@ -441,6 +492,7 @@ fn object_property(i: TokenSlice) -> PResult<ObjectProperty> {
 
 /// Parse a KCL object value.
 fn object(i: TokenSlice) -> PResult<ObjectExpression> {
+    let tokens = *i;
     let start = open_brace(i)?.start;
     ignore_whitespace(i);
     let properties = separated0(object_property, comma_sep)
@@ -449,8 +501,17 @@ fn object(i: TokenSlice) -> PResult<ObjectExpression> {
         ))
         .parse_next(i)?;
     ignore_whitespace(i);
-    let end = close_brace(i)?.end;
-    Ok(ObjectExpression { start, end, properties })
+    let close = close_brace(i)?;
+    let line_breaks = line_breaks(
+        tokens,
+        properties.iter().map(|property| property.start).chain([close.start]),
+    );
+    Ok(ObjectExpression {
+        start,
+        end: close.end,
+        properties,
+        line_breaks,
+    })
 }
 
 /// Parse the % symbol, used to substitute a curried argument from a |> (pipe).
@@ -506,11 +567,12 @@ fn member_expression_dot(i: TokenSlice) -> PResult<(LiteralIdentifier, usize, bo
     Ok((LiteralIdentifier::Identifier(Box::new(property)), end, false))
 }
 
-/// E.g. `people[0]` or `people[i]` or `people['adam']`
+/// E.g. `people[0]` or `people[-1]` (the last person) or `people[i]` or `people['adam']`
 fn member_expression_subscript(i: TokenSlice) -> PResult<(LiteralIdentifier, usize, bool)> {
     let _ = open_bracket.parse_next(i)?;
     let property = alt((
         literal.map(Box::new).map(LiteralIdentifier::Literal),
+        negative_number_literal.map(Box::new).map(LiteralIdentifier::Literal),
         identifier.map(Box::new).map(LiteralIdentifier::Identifier),
     ))
     .parse_next(i)?;
@@ -633,14 +695,14 @@ fn body_items_within_function(i: TokenSlice) -> PResult<WithinFunction> {
     // If there is a comment, it may be preceded by whitespace.
     let item = dispatch! {peek(any);
         token if token.declaration_keyword().is_some() =>
-            (declaration.map(BodyItem::VariableDeclaration), opt(noncode_just_after_code)).map(WithinFunction::BodyItem),
+            (terminated(declaration.map(BodyItem::VariableDeclaration), opt(semicolon)), opt(noncode_just_after_code)).map(WithinFunction::BodyItem),
         Token { ref value, .. } if value == "return" =>
-            (return_stmt.map(BodyItem::ReturnStatement), opt(noncode_just_after_code)).map(WithinFunction::BodyItem),
+            (terminated(return_stmt.map(BodyItem::ReturnStatement), opt(semicolon)), opt(noncode_just_after_code)).map(WithinFunction::BodyItem),
         token if !token.is_code_token() => {
             non_code_node.map(WithinFunction::NonCode)
         },
         _ =>
-            (expression.map(BodyItem::ExpressionStatement), opt(noncode_just_after_code)).map(WithinFunction::BodyItem),
+            (terminated(expression.map(BodyItem::ExpressionStatement), opt(semicolon)), opt(noncode_just_after_code)).map(WithinFunction::BodyItem),
     }
     .context(expected("a function body items (functions are made up of variable declarations, expressions, and return statements, each of those is a possible body item"))
     .parse_next(i)?;
@@ -959,7 +1021,7 @@ impl TryFrom<Token> for Identifier {
             Ok(Identifier {
                 start: token.start,
                 end: token.end,
-                name: token.value,
+                name: token.value.as_str().into(),
             })
         } else {
             Err(KclError::Syntax(KclErrorDetails {
@@ -990,6 +1052,10 @@ fn require_whitespace(i: TokenSlice) -> PResult<()> {
     repeat(1.., whitespace).parse_next(i)
 }
 
+/// Parse a unary expression, e.g. `-x`.
+/// A `-` is only unary where an operand is expected: at the start of an expression, or after
+/// an operator, `(`, `[` or `,`. Right after an operand it's always subtraction however it's
+/// spaced, so `a -b` is `a - b`, and `f(a -b)` is a call with one argument.
 fn unary_expression(i: TokenSlice) -> PResult<UnaryExpression> {
     const EXPECTED: &str = "expected a unary operator (like '-', the negative-numeric operator),";
     let (operator, op_token) = any
@@ -1061,7 +1127,8 @@ fn binary_expr_in_parens(i: TokenSlice) -> PResult<BinaryExpression> {
     let span_with_brackets = bracketed_section.recognize().parse_next(i)?;
     let n = span_with_brackets.len();
     let mut span_no_brackets = &span_with_brackets[1..n - 1];
-    let expr = binary_expression.parse_next(&mut span_no_brackets)?;
+    let mut expr = binary_expression.parse_next(&mut span_no_brackets)?;
+    expr.parenthesized = Some(true);
     Ok(expr)
 }
 
@@ -1184,6 +1251,12 @@ fn comma(i: TokenSlice) -> PResult<()> {
     Ok(())
 }
 
+/// A `;` at the end of a statement.
+fn semicolon(i: TokenSlice) -> PResult<()> {
+    TokenType::Semicolon.parse_from(i)?;
+    Ok(())
+}
+
 fn period(i: TokenSlice) -> PResult<()> {
     TokenType::Period.parse_from(i)?;
     Ok(())
@@ -1251,7 +1324,7 @@ fn parameters(i: TokenSlice) -> PResult<Vec<Identifier>> {
 impl Identifier {
     fn into_valid_binding_name(self) -> Result<Identifier, KclError> {
         // Make sure they are not assigning a variable to a stdlib function.
-        if STDLIB.fns.contains_key(&self.name) {
+        if STDLIB.fns.contains_key(self.name.as_str()) {
             return Err(KclError::Syntax(KclErrorDetails {
                 source_ranges: vec![SourceRange([self.start, self.end])],
                 message: format!("Cannot assign a variable to a reserved keyword: {}", self.name),
@@ -1274,6 +1347,10 @@ fn fn_call(i: TokenSlice) -> PResult<CallExpression> {
     let fn_name = identifier(i)?;
     let _ = terminated(open_paren, opt(whitespace)).parse_next(i)?;
     let args = arguments(i)?;
+    if !args.is_empty() {
+        // Allow a trailing comma, but not a lone one in `f(,)`.
+        opt((opt(whitespace), comma)).parse_next(i)?;
+    }
     let end = preceded(opt(whitespace), close_paren).parse_next(i)?.end;
     let function = if let Some(stdlib_fn) = STDLIB.get(&fn_name.name) {
         crate::ast::types::Function::StdLib { func: stdlib_fn }
@@ -2065,4 +2142,103 @@ const mySk1 = startSketchAt([0, 0])"#;
             assert_eq!(value.raw, "5");
         }
     }
+
+    #[test]
+    fn test_unicode_identifiers() {
+        let code = "const größe = 2\nconst café = größe * 3";
+        let program = crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+
+        let BodyItem::VariableDeclaration(first) = &program.body[0] else {
+            panic!("Expected variable declaration");
+        };
+        let id = &first.declarations[0].id;
+        assert_eq!(id.name, "größe");
+        // Spans are byte offsets, and ö and ß are two bytes each.
+        assert_eq!((id.start, id.end), (6, 13));
+        assert_eq!(&code[id.start..id.end], "größe");
+
+        let BodyItem::VariableDeclaration(second) = &program.body[1] else {
+            panic!("Expected variable declaration");
+        };
+        let Value::BinaryExpression(init) = &second.declarations[0].init else {
+            panic!("Expected binary expression");
+        };
+        assert_eq!(&code[init.left.start()..init.left.end()], "größe");
+
+        assert_eq!(program.recast(&Default::default(), 0), format!("{code}\n"));
+    }
+
+    #[test]
+    fn test_negative_array_index() {
+        for code in ["const last = xs[-1]", "const item = xs[i]", "const x = xs[-2][0].y"] {
+            let program = crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+            assert_eq!(program.recast(&Default::default(), 0), format!("{code}\n"));
+        }
+
+        let program = crate::parser::Parser::new(crate::token::lexer("const last = xs[-1]"))
+            .ast()
+            .unwrap();
+        let BodyItem::VariableDeclaration(declaration) = &program.body[0] else {
+            panic!("Expected variable declaration");
+        };
+        let Value::MemberExpression(member) = &declaration.declarations[0].init else {
+            panic!("Expected member expression");
+        };
+        let LiteralIdentifier::Literal(index) = &member.property else {
+            panic!("Expected a literal index");
+        };
+        assert_eq!(index.value, JValue::from(-1));
+        assert_eq!((index.start, index.end), (16, 18));
+        assert!(!member.computed);
+    }
+
+    #[test]
+    fn test_ambiguous_unary_minus() {
+        let parse = |code: &str| crate::parser::Parser::new(crate::token::lexer(code)).ast().unwrap();
+        let expression = |program: &Program| {
+            let BodyItem::ExpressionStatement(statement) = &program.body[0] else {
+                panic!("Expected expression statement");
+            };
+            statement.expression.clone()
+        };
+
+        // After an operand, `-` subtracts, even with no space before the right operand.
+        let program = parse("a -b");
+        let Value::BinaryExpression(binary) = expression(&program) else {
+            panic!("Expected binary expression");
+        };
+        assert_eq!(binary.operator, BinaryOperator::Sub);
+        assert_eq!((binary.start, binary.end), (0, 4));
+        assert_eq!((binary.right.start(), binary.right.end()), (3, 4));
+
+        // So the call has one argument, `a - b`.
+        let program = parse("f(a -b)");
+        let Value::CallExpression(call) = expression(&program) else {
+            panic!("Expected call expression");
+        };
+        assert_eq!(call.arguments.len(), 1);
+        let Value::BinaryExpression(binary) = &call.arguments[0] else {
+            panic!("Expected binary expression");
+        };
+        assert_eq!(binary.operator, BinaryOperator::Sub);
+        assert_eq!((binary.start, binary.end), (2, 6));
+
+        // Where an operand is expected, `-` negates.
+        let program = parse("f(a, -b)");
+        let Value::CallExpression(call) = expression(&program) else {
+            panic!("Expected call expression");
+        };
+        assert_eq!(call.arguments.len(), 2);
+        let Value::UnaryExpression(unary) = &call.arguments[1] else {
+            panic!("Expected unary expression");
+        };
+        assert_eq!(unary.operator, UnaryOperator::Neg);
+        assert_eq!((unary.start, unary.end), (5, 7));
+
+        let program = parse("a - -b");
+        let Value::BinaryExpression(binary) = expression(&program) else {
+            panic!("Expected binary expression");
+        };
+        assert!(matches!(binary.right, BinaryPart::UnaryExpression(_)));
+    }
 }
@@ -544,6 +544,11 @@ impl LanguageServer for Backend {
             return Ok(None);
         };
 
+        // Don't rename to something that wouldn't parse back as the same name.
+        if !crate::token::is_valid_identifier(&params.new_name) {
+            return Ok(None);
+        }
+
         // Parse the ast.
         // I don't know if we need to do this again since it should be updated in the context.
         // But I figure better safe than sorry since this will write back out to the file.
@@ -558,6 +563,7 @@ impl LanguageServer for Backend {
                 tab_size: params.options.tab_size as usize,
                 insert_final_newline: params.options.insert_final_newline.unwrap_or(false),
                 use_tabs: !params.options.insert_spaces,
+                ..Default::default()
             },
             0,
         );
@@ -576,6 +582,11 @@ impl LanguageServer for Backend {
             return Ok(None);
         };
 
+        // Don't rename to something that wouldn't parse back as the same name.
+        if !crate::token::is_valid_identifier(&params.new_name) {
+            return Ok(None);
+        }
+
         // Parse the ast.
         // I don't know if we need to do this again since it should be updated in the context.
         // But I figure better safe than sorry since this will write back out to the file.
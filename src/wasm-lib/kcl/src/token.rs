@@ -38,6 +38,8 @@ pub enum TokenType {
     Period,
     /// A double period: `..`.
     DoublePeriod,
+    /// A semicolon, which may end a statement.
+    Semicolon,
     /// A line comment.
     LineComment,
     /// A block comment.
@@ -64,7 +66,8 @@ impl TryFrom<TokenType> for SemanticTokenType {
             | TokenType::Comma
             | TokenType::Colon
             | TokenType::Period
-            | TokenType::DoublePeriod => {
+            | TokenType::DoublePeriod
+            | TokenType::Semicolon => {
                 anyhow::bail!("unsupported token type: {:?}", token_type)
             }
         })
@@ -182,10 +185,46 @@ impl From<&Token> for SourceRange {
     }
 }
 
+/// Words with a meaning of their own, which can't be used as names.
+pub const KEYWORDS: &[&str] = &[
+    "if", "else", "for", "while", "return", "break", "continue", "fn", "let", "mut", "loop", "true", "false", "nil",
+    "and", "or", "not", "var", "const",
+];
+
+/// Whether a character can start an identifier: `_` or any XID_Start character.
+pub fn is_identifier_start(c: char) -> bool {
+    c == '_' || unicode_ident::is_xid_start(c)
+}
+
+/// Whether a character can appear after the first character of an identifier.
+pub fn is_identifier_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
+
+/// Whether a word is a keyword, so can't be used as a name.
+pub fn is_reserved(name: &str) -> bool {
+    KEYWORDS.contains(&name)
+}
+
+/// Whether a string lexes as a single identifier that isn't a keyword.
+pub fn is_valid_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(is_identifier_start) && chars.all(is_identifier_continue) && !is_reserved(name)
+}
+
 pub fn lexer(s: &str) -> Vec<Token> {
     tokeniser::lexer(s).unwrap_or_default()
 }
 
+/// The token covering a byte offset in the source, including whitespace and comments.
+/// A token covers its start but not its end, so an offset between two tokens gets the second.
+/// Returns `None` past the end of the source, or if it doesn't lex.
+pub fn token_at(src: &str, offset: usize) -> Option<Token> {
+    lexer(src)
+        .into_iter()
+        .find(|token| token.start <= offset && offset < token.end)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -196,4 +235,36 @@ mod tests {
         let semantic_types = TokenType::all_semantic_token_types().unwrap();
         assert!(!semantic_types.is_empty());
     }
+
+    #[test]
+    fn test_is_valid_identifier() {
+        for valid in ["x", "_x", "part001", "café", "größe", "δ", "長さ"] {
+            assert!(is_valid_identifier(valid), "{valid} should be valid");
+        }
+        for invalid in ["", "1x", "a-b", "a b", "€", "const", "return"] {
+            assert!(!is_valid_identifier(invalid), "{invalid} should be invalid");
+        }
+        assert!(is_reserved("fn"));
+        assert!(!is_reserved("fnord"));
+    }
+
+    #[test]
+    fn test_token_at() {
+        let code = "const x = width + 1";
+
+        let plus = token_at(code, 16).unwrap();
+        assert_eq!(plus.token_type, TokenType::Operator);
+        assert_eq!(plus.value, "+");
+        assert_eq!((plus.start, plus.end), (16, 17));
+
+        for offset in [10, 12, 14] {
+            let width = token_at(code, offset).unwrap();
+            assert_eq!(width.token_type, TokenType::Word);
+            assert_eq!(width.value, "width");
+            assert_eq!((width.start, width.end), (10, 15));
+        }
+
+        assert_eq!(token_at(code, 15).unwrap().token_type, TokenType::Whitespace);
+        assert!(token_at(code, code.len()).is_none());
+    }
 }
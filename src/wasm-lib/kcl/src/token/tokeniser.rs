@@ -22,6 +22,7 @@ pub fn token(i: &mut Located<&str>) -> PResult<Token> {
         ',' => comma,
         '0'..='9' => number,
         ':' => colon,
+        ';' => semicolon,
         '.' => alt((number, double_period, period)),
         ' ' | '\t' | '\n' => whitespace,
         _ => alt((operator, keyword, word))
@@ -57,9 +58,11 @@ fn whitespace(i: &mut Located<&str>) -> PResult<Token> {
     Ok(Token::from_range(range, TokenType::Whitespace, value.to_string()))
 }
 
+/// An identifier is an XID_Start character or `_`, followed by any number of XID_Continue
+/// characters (letters, digits, `_` and combining marks, in any script).
 fn inner_word(i: &mut Located<&str>) -> PResult<()> {
-    one_of(('a'..='z', 'A'..='Z', '_')).parse_next(i)?;
-    repeat(0.., one_of(('a'..='z', 'A'..='Z', '0'..='9', '_'))).parse_next(i)?;
+    one_of(crate::token::is_identifier_start).parse_next(i)?;
+    repeat(0.., one_of(crate::token::is_identifier_continue)).parse_next(i)?;
     Ok(())
 }
 
@@ -97,6 +100,11 @@ fn colon(i: &mut Located<&str>) -> PResult<Token> {
     Ok(Token::from_range(range, TokenType::Colon, value.to_string()))
 }
 
+fn semicolon(i: &mut Located<&str>) -> PResult<Token> {
+    let (value, range) = ';'.with_span().parse_next(i)?;
+    Ok(Token::from_range(range, TokenType::Semicolon, value.to_string()))
+}
+
 fn period(i: &mut Located<&str>) -> PResult<Token> {
     let (value, range) = '.'.with_span().parse_next(i)?;
     Ok(Token::from_range(range, TokenType::Period, value.to_string()))
@@ -139,7 +147,7 @@ fn keyword(i: &mut Located<&str>) -> PResult<Token> {
     // the start of a normal word.
     let keyword = terminated(
         keyword_candidates,
-        peek(none_of(('a'..='z', 'A'..='Z', '-', '_', '0'..='9'))),
+        peek(none_of(|c: char| c == '-' || crate::token::is_identifier_continue(c))),
     );
     let (value, range) = keyword.with_span().parse_next(i)?;
     Ok(Token::from_range(range, TokenType::Keyword, value.to_owned()))
@@ -191,15 +199,24 @@ mod tests {
 
     #[test]
     fn test_word() {
-        for valid in ["a", "a ", "a5", "a5a"] {
+        for valid in ["a", "a ", "a5", "a5a", "_a", "café", "größe2", "δx", "長さ"] {
             assert_parse_ok(word, valid);
         }
 
-        for invalid in ["5", "5a", "5a5"] {
+        for invalid in ["5", "5a", "5a5", "\u{301}a", "€"] {
             assert_parse_err(word, invalid);
         }
     }
 
+    #[test]
+    fn test_unicode_word_span() {
+        let tokens = lexer("const café = 1").unwrap();
+        let word = &tokens[2];
+        assert_eq!(word.token_type, TokenType::Word);
+        assert_eq!(word.value, "café");
+        assert_eq!((word.start, word.end), (6, 11));
+    }
+
     #[test]
     fn test_operator() {
         for valid in [
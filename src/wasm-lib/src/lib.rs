@@ -107,6 +107,13 @@ pub fn parse_wasm(js: &str) -> Result<JsValue, String> {
     JsValue::from_serde(&program).map_err(|e| e.to_string())
 }
 
+// Parse with error recovery and lint, so the editor gets the AST and every diagnostic in one call.
+#[wasm_bindgen]
+pub fn analyze_wasm(js: &str) -> Result<JsValue, String> {
+    let result = kcl_lib::ast::diagnostics::analyze(js);
+    JsValue::from_serde(&result).map_err(|e| e.to_string())
+}
+
 // wasm_bindgen wrapper for recast
 // test for this function and by extension the recaster are done in javascript land src/lang/recast.test.ts
 #[wasm_bindgen]